@@ -0,0 +1,72 @@
+fn main() {
+    // Features add up, e.g. with --all-features, so both can be on at once.
+    // zlib-ng wins, it's the more specific choice.
+    #[cfg(all(feature = "bundled", feature = "zlib-ng"))]
+    println!("cargo:warning=both `bundled` and `zlib-ng` are enabled, linking zlib-ng");
+    #[cfg(all(feature = "bundled", not(feature = "zlib-ng")))]
+    build_zlib();
+    #[cfg(feature = "zlib-ng")]
+    link_zlib_ng();
+}
+
+// Compiles ./zlib into a static libz, the same set of files zlib's own
+// Makefile builds, and tells cargo to link it.
+#[cfg(all(feature = "bundled", not(feature = "zlib-ng")))]
+fn build_zlib() {
+    const SOURCES: [&str; 15] = [
+        "adler32.c",
+        "compress.c",
+        "crc32.c",
+        "deflate.c",
+        "gzclose.c",
+        "gzlib.c",
+        "gzread.c",
+        "gzwrite.c",
+        "infback.c",
+        "inffast.c",
+        "inflate.c",
+        "inftrees.c",
+        "trees.c",
+        "uncompr.c",
+        "zutil.c",
+    ];
+
+    let mut build = cc::Build::new();
+    build.include("zlib").warnings(false);
+    for source in SOURCES {
+        build.file(format!("zlib/{}", source));
+    }
+    // What zlib's configure would find on a typical system.
+    build.define("STDC", None);
+    build.define("HAVE_SYS_TYPES_H", None);
+    build.define("HAVE_STDINT_H", None);
+    build.define("HAVE_STDDEF_H", None);
+    let target_os = std::env::var("CARGO_CFG_TARGET_OS").unwrap_or_default();
+    if target_os != "windows" {
+        build.define("HAVE_UNISTD_H", None);
+    }
+    // gzseek64/gztell64 are only compiled in with it.
+    if cfg!(feature = "largefile") {
+        build.define("_LARGEFILE64_SOURCE", Some("1"));
+    }
+    build.compile("z");
+
+    println!("cargo:rerun-if-changed=zlib");
+}
+
+// zlib-ng built with ZLIB_COMPAT=ON is a libz with the same symbols as zlib,
+// usually installed away from the system one, so cargo is told where it is.
+// Without ZLIB_NG_LIB_DIR it's looked for where the linker looks by default,
+// where distributions packaging zlib-ng-compat put it in place of zlib. Not
+// libz-ng, zlib-ng's native build, its functions are all prefixed with zng_.
+#[cfg(feature = "zlib-ng")]
+fn link_zlib_ng() {
+    println!("cargo:rerun-if-env-changed=ZLIB_NG_LIB_DIR");
+    match std::env::var("ZLIB_NG_LIB_DIR") {
+        Ok(dir) => println!("cargo:rustc-link-search=native={}", dir),
+        Err(_) => println!(
+            "cargo:warning=ZLIB_NG_LIB_DIR isn't set, linking the libz on the default search path"
+        ),
+    }
+    println!("cargo:rustc-link-lib=z");
+}
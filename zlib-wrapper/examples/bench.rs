@@ -0,0 +1,163 @@
+// The timings quoted in the doc comments, run them with
+// `cargo run --release --example bench [name]`, all of them without a name.
+use std::io::Write;
+use std::time::{Duration, Instant};
+
+use zlib_wrapper::*;
+
+// Each measurement runs this many rounds, the first and the fastest are printed.
+const ROUNDS: usize = 5;
+
+// Time per call of `f` over `calls` calls, for every round.
+fn per_call(calls: u32, mut f: impl FnMut()) -> Vec<Duration> {
+    (0..ROUNDS)
+        .map(|_| {
+            let start = Instant::now();
+            for _ in 0..calls {
+                f();
+            }
+            start.elapsed() / calls
+        })
+        .collect()
+}
+
+fn report(what: &str, rounds: &[Duration]) {
+    let best = rounds.iter().min().unwrap();
+    println!("{what}: {:?} first round, {best:?} best", rounds[0]);
+}
+
+// 200 byte messages, the size the small message numbers are about.
+fn messages() -> Vec<Vec<u8>> {
+    (0..1000)
+        .map(|i| {
+            let message =
+                format!("{{\"id\": {i}, \"name\": \"user{i}\", \"tags\": [\"a\", \"b\"], ");
+            let mut message = message.into_bytes();
+            message.resize(200, b' ');
+            message
+        })
+        .collect()
+}
+
+// About 1 MB of text that doesn't repeat.
+fn megabyte() -> Vec<u8> {
+    let mut text: Vec<u8> = (0..200000)
+        .flat_map(|i| format!("{i} ").into_bytes())
+        .collect();
+    text.truncate(1 << 20);
+    text
+}
+
+// `zlib_compress` against `zlib_compress_into` with a reused buffer.
+fn compress_into() {
+    let messages = messages();
+    let mut next = messages.iter().cycle();
+    report(
+        "zlib_compress, 200 bytes",
+        &per_call(10000, || {
+            zlib_compress(next.next().unwrap()).unwrap();
+        }),
+    );
+    let mut dest = Vec::new();
+    report(
+        "zlib_compress_into, 200 bytes",
+        &per_call(10000, || {
+            dest.clear();
+            zlib_compress_into(next.next().unwrap(), &mut dest).unwrap();
+        }),
+    );
+    let megabyte = megabyte();
+    report(
+        "zlib_compress, 1 MB",
+        &per_call(20, || {
+            zlib_compress(&megabyte).unwrap();
+        }),
+    );
+    report(
+        "zlib_compress_into, 1 MB",
+        &per_call(20, || {
+            dest.clear();
+            zlib_compress_into(&megabyte, &mut dest).unwrap();
+        }),
+    );
+}
+
+// Compresses `message` into `output` and finishes the stream.
+fn deflate(deflater: &mut Deflater, message: &[u8], output: &mut [u8]) {
+    let (_, produced) = deflater.compress(message, output).unwrap();
+    let (_, done) = deflater.finish(&mut output[produced..]).unwrap();
+    assert!(done);
+}
+
+// `Deflater::reset` against a new `Deflater` for every message.
+fn reset() {
+    let messages = messages();
+    let mut output = vec![0u8; compress_bound(200) + 16];
+    let mut next = messages.iter().cycle();
+    report(
+        "new Deflater, 200 bytes",
+        &per_call(10000, || {
+            let mut deflater = Deflater::new(CompressionLevel::Default, Format::Zlib).unwrap();
+            deflate(&mut deflater, next.next().unwrap(), &mut output);
+        }),
+    );
+    let mut deflater = Deflater::new(CompressionLevel::Default, Format::Zlib).unwrap();
+    report(
+        "Deflater::reset, 200 bytes",
+        &per_call(10000, || {
+            deflater.reset().unwrap();
+            deflate(&mut deflater, next.next().unwrap(), &mut output);
+        }),
+    );
+}
+
+// `read_gz_file` throughput for the gzread buffer sizes it could use, on a
+// file of 112 MB of text.
+fn read_buffer() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("lines.gz");
+    let path = path.to_str().unwrap();
+    let mut writer = GzWriter::create(path).unwrap();
+    let mut len = 0;
+    for i in 0.. {
+        let line = format!("{i}\n");
+        writer.write_all(line.as_bytes()).unwrap();
+        len += line.len();
+        if len >= 112 << 20 {
+            break;
+        }
+    }
+    writer.close().unwrap();
+    for buf_size in [0x1000, 0x40000, 0x100000] {
+        let rounds: Vec<Duration> = (0..ROUNDS)
+            .map(|_| {
+                let start = Instant::now();
+                read_gz_file_with_capacity(path, buf_size).unwrap();
+                start.elapsed()
+            })
+            .collect();
+        let best = rounds.iter().min().unwrap();
+        let rate = |elapsed: &Duration| len as f64 / elapsed.as_secs_f64() / 1e6;
+        println!(
+            "{} KB buffer: {:.0} MB/s first round, {:.0} MB/s best",
+            buf_size >> 10,
+            rate(&rounds[0]),
+            rate(best)
+        );
+    }
+}
+
+fn main() {
+    let benches: &[(&str, fn())] = &[
+        ("compress_into", compress_into),
+        ("reset", reset),
+        ("read_buffer", read_buffer),
+    ];
+    let name = std::env::args().nth(1);
+    for (bench, run) in benches {
+        if name.as_deref().is_none_or(|name| name == *bench) {
+            println!("{bench}");
+            run();
+        }
+    }
+}
@@ -0,0 +1,106 @@
+// A short tour of the library, run it with `cargo run --example tour`. What
+// every function should do is checked by the tests under tests/.
+use std::io::{BufRead, BufReader, Read, Write};
+
+use zlib_wrapper::*;
+
+fn main() -> Result<(), ZlibError> {
+    println!("zlib {}", zlib_version());
+    check_zlib_version()?;
+
+    println!("1. compress/decompress");
+
+    let hello_zlib = "hello, zlib, no exclamation mark".as_bytes();
+    let compressed = zlib_compress(hello_zlib)?;
+    let uncompressed = zlib_uncompress(&compressed, 100)?;
+    println!(
+        "{} bytes -> {} bytes: {}",
+        hello_zlib.len(),
+        compressed.len(),
+        String::from_utf8_lossy(&uncompressed)
+    );
+
+    let lorem = "Lorem ipsum dolor sit amet, consectetur adipiscing elit. ".repeat(100);
+    for level in [
+        CompressionLevel::None,
+        CompressionLevel::Fast,
+        CompressionLevel::Default,
+        CompressionLevel::Best,
+    ] {
+        let compressed = zlib_compress_level(&lorem, level)?;
+        println!("{} bytes at {:?}: {}", lorem.len(), level, compressed.len());
+    }
+
+    println!("2. checksums");
+
+    println!("crc32(\"123456789\") = {:#010x}", crc32(b"123456789"));
+    println!("adler32(\"Wikipedia\") = {:#010x}", adler32(b"Wikipedia"));
+
+    println!("3. Deflater, Inflater");
+
+    let mut deflater = Deflater::new(CompressionLevel::Default, Format::Raw)?;
+    let mut deflated = vec![0u8; deflater.bound(lorem.len())];
+    let (_, produced) = deflater.compress(lorem.as_bytes(), &mut deflated)?;
+    let (finished, _) = deflater.finish(&mut deflated[produced..])?;
+    deflated.truncate(produced + finished);
+    let mut inflater = Inflater::new(Format::Raw)?;
+    let mut inflated = vec![0u8; lorem.len()];
+    let (_, produced, done) = inflater.decompress(&deflated, &mut inflated)?;
+    println!(
+        "{} bytes -> {} bytes of raw deflate -> {} bytes, done: {}",
+        lorem.len(),
+        deflated.len(),
+        produced,
+        done
+    );
+
+    println!("4. gz files");
+
+    println!("{}", read_gz_file("file.txt.gz")?);
+    let reader = BufReader::new(GzReader::open("file.txt.gz")?);
+    for (n, line) in reader.lines().enumerate() {
+        println!("{}: {}", n + 1, line?);
+    }
+
+    let dir = tempfile::tempdir()?;
+    let hello_gz = dir.path().join("hello.txt.gz");
+    let hello_gz = hello_gz.to_str().ok_or(ZlibError::StreamError)?;
+    let mut writer = GzWriter::create(hello_gz)?;
+    writer.write_all(b"hello, gzip\n")?;
+    writer.close()?;
+    print!("{}", read_gz_file(hello_gz)?);
+
+    println!("5. GzEncoder, GzDecoder");
+
+    let header = GzipHeaderBuilder::new().name("lorem.txt").build();
+    let mut encoder = GzEncoder::with_header(Vec::new(), CompressionLevel::Best, &header)?;
+    encoder.write_all(lorem.as_bytes())?;
+    let gzipped = encoder.finish()?;
+    let mut decoder = GzDecoder::new(&gzipped[..])?;
+    let mut decoded = String::new();
+    decoder.read_to_string(&mut decoded)?;
+    println!(
+        "{} bytes -> {} bytes -> {} bytes, named {:?}",
+        lorem.len(),
+        gzipped.len(),
+        decoded.len(),
+        decoder.header().and_then(|header| header.name)
+    );
+
+    println!("6. compress_parallel");
+
+    let numbers: String = (0..600000).map(|i| format!("{i} ")).collect();
+    let start = std::time::Instant::now();
+    let parallel = compress_parallel(numbers.as_bytes(), CompressionLevel::Default, 4)?;
+    let elapsed = start.elapsed();
+    let mut streamed = Vec::new();
+    decompress_stream(&parallel[..], &mut streamed)?;
+    println!(
+        "{} bytes -> {} bytes in {:?} on 4 threads -> {} bytes",
+        numbers.len(),
+        parallel.len(),
+        elapsed,
+        streamed.len()
+    );
+    Ok(())
+}
@@ -0,0 +1,332 @@
+use core::pin::Pin;
+use core::task::{Context, Poll, ready};
+
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+use crate::compress::CompressionLevel;
+use crate::deflate::{Deflater, Z_SYNC_FLUSH};
+use crate::error::ZlibError;
+use crate::header::GzipHeader;
+use crate::inflate::Inflater;
+use crate::stream::Format;
+
+// At most this much is decompressed in one poll, so a single poll with a huge
+// buffer doesn't hold up the other tasks on the thread for long.
+const MAX_OUTPUT_PER_POLL: usize = 0x10000;
+
+// Same as `GzDecoder`, for tokio: decompresses a gzip member read from `inner`,
+// e.g. a request body, without blocking the runtime while waiting for it.
+// Only the first member is read, like `GzDecoder`, `AsyncMultiGzDecoder`
+// reads them all.
+// The inflating itself is done right in `poll_read`, on at most 32 KB of input
+// into at most 64 KB of output at a time, a fraction of a millisecond. When the
+// inner reader has a lot of input ready that decompresses to nothing, e.g. a
+// huge extra field in the header, a poll keeps going until output comes out
+// and can still block the thread for a while.
+pub struct AsyncGzDecoder<R: AsyncRead> {
+    inner: R,
+    inflater: Inflater,
+    // Compressed bytes read from `inner`, `buffer[pos..len]` are not inflated yet.
+    buffer: Vec<u8>,
+    pos: usize,
+    len: usize,
+    done: bool,
+    // Same as in `InflateReader`, for `AsyncMultiGzDecoder`.
+    multi: bool,
+    max_output: Option<u64>,
+    earlier_out: u64,
+}
+
+impl<R: AsyncRead> AsyncGzDecoder<R> {
+    pub fn new(inner: R) -> Result<AsyncGzDecoder<R>, ZlibError> {
+        AsyncGzDecoder::with_multi(inner, false)
+    }
+
+    fn with_multi(inner: R, multi: bool) -> Result<AsyncGzDecoder<R>, ZlibError> {
+        let mut inflater = Inflater::new(Format::Gzip)?;
+        inflater.track_header()?;
+        Ok(AsyncGzDecoder {
+            inner,
+            inflater,
+            buffer: vec![0; 0x8000],
+            pos: 0,
+            len: 0,
+            done: false,
+            multi,
+            max_output: None,
+            earlier_out: 0,
+        })
+    }
+
+    // Same as `Inflater::set_max_output`, reads fail with `LimitExceeded`.
+    pub fn set_max_output(&mut self, max_output: Option<u64>) {
+        self.max_output = max_output;
+        let left = max_output.map(|max| max.saturating_sub(self.earlier_out));
+        self.inflater.set_max_output(left);
+    }
+
+    // Same as `Inflater::set_max_ratio`, reads fail with `LimitExceeded`.
+    pub fn set_max_ratio(&mut self, max_ratio: Option<u64>) {
+        self.inflater.set_max_ratio(max_ratio);
+    }
+
+    // The header of the member, once enough of it has been read.
+    pub fn header(&self) -> Option<GzipHeader> {
+        self.inflater.header()
+    }
+
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    // Reading from the inner reader directly corrupts the decompressed output.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    // Compressed bytes already read from the inner reader but not decompressed
+    // are lost, including anything past the end of the member.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for AsyncGzDecoder<R> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        let this = self.get_mut();
+        if buf.remaining() == 0 {
+            return Poll::Ready(Ok(()));
+        }
+        loop {
+            if this.done {
+                if !this.multi {
+                    return Poll::Ready(Ok(()));
+                }
+                // Nothing was decompressed in this poll yet, it can be pending.
+                if this.pos == this.len {
+                    let mut input = ReadBuf::new(&mut this.buffer);
+                    ready!(Pin::new(&mut this.inner).poll_read(cx, &mut input))?;
+                    this.len = input.filled().len();
+                    this.pos = 0;
+                    if this.len == 0 {
+                        return Poll::Ready(Ok(()));
+                    }
+                }
+                this.earlier_out += this.inflater.total_out();
+                this.inflater.reset()?;
+                this.set_max_output(this.max_output);
+                this.done = false;
+            }
+            if this.pos == this.len {
+                let mut input = ReadBuf::new(&mut this.buffer);
+                ready!(Pin::new(&mut this.inner).poll_read(cx, &mut input))?;
+                this.len = input.filled().len();
+                this.pos = 0;
+                if this.len == 0 {
+                    return Poll::Ready(Err(std::io::ErrorKind::UnexpectedEof.into()));
+                }
+            }
+            let input = &this.buffer[this.pos..this.len];
+            let output = buf.initialize_unfilled_to(buf.remaining().min(MAX_OUTPUT_PER_POLL));
+            let (consumed, produced, done) = this.inflater.decompress(input, output)?;
+            buf.advance(produced);
+            this.pos += consumed;
+            this.done = done;
+            if produced > 0 {
+                return Poll::Ready(Ok(()));
+            }
+            if consumed == 0 && !done {
+                return Poll::Ready(Err(ZlibError::DataError.into()));
+            }
+        }
+    }
+}
+
+// Same as `MultiGzDecoder`, for tokio: reads every gzip member in `inner` one
+// after another, until it ends.
+pub struct AsyncMultiGzDecoder<R: AsyncRead> {
+    decoder: AsyncGzDecoder<R>,
+}
+
+impl<R: AsyncRead> AsyncMultiGzDecoder<R> {
+    pub fn new(inner: R) -> Result<AsyncMultiGzDecoder<R>, ZlibError> {
+        Ok(AsyncMultiGzDecoder {
+            decoder: AsyncGzDecoder::with_multi(inner, true)?,
+        })
+    }
+
+    // Same as `Inflater::set_max_output`, over all the members together.
+    pub fn set_max_output(&mut self, max_output: Option<u64>) {
+        self.decoder.set_max_output(max_output);
+    }
+
+    // Same as `Inflater::set_max_ratio`, for each member.
+    pub fn set_max_ratio(&mut self, max_ratio: Option<u64>) {
+        self.decoder.set_max_ratio(max_ratio);
+    }
+
+    // The header of the member being read, once enough of it has been read.
+    pub fn header(&self) -> Option<GzipHeader> {
+        self.decoder.header()
+    }
+
+    pub fn get_ref(&self) -> &R {
+        self.decoder.get_ref()
+    }
+
+    // Reading from the inner reader directly corrupts the decompressed output.
+    pub fn get_mut(&mut self) -> &mut R {
+        self.decoder.get_mut()
+    }
+
+    // Compressed bytes already read from the inner reader but not decompressed
+    // are lost.
+    pub fn into_inner(self) -> R {
+        self.decoder.into_inner()
+    }
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for AsyncMultiGzDecoder<R> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.get_mut().decoder).poll_read(cx, buf)
+    }
+}
+
+// Same as `GzEncoder`, for tokio: compresses everything written to it into a
+// gzip member, writing that to `inner` as it goes, e.g. a response body to a
+// socket. `shutdown` writes the trailer, there's no `Drop` doing it, as that
+// can't wait for `inner`. Compressed bytes `inner` didn't take yet are kept
+// and written first thing in the next poll, so a write returning means its
+// data was compressed, a flush that it reached `inner`.
+pub struct AsyncGzEncoder<W: AsyncWrite> {
+    inner: W,
+    deflater: Deflater,
+    // Compressed bytes, `buffer[pos..len]` are not written to `inner` yet.
+    buffer: Vec<u8>,
+    pos: usize,
+    len: usize,
+    // Whether the trailer is in `buffer` or written already.
+    finished: bool,
+}
+
+impl<W: AsyncWrite> AsyncGzEncoder<W> {
+    pub fn new(inner: W, level: CompressionLevel) -> Result<AsyncGzEncoder<W>, ZlibError> {
+        Ok(AsyncGzEncoder {
+            inner,
+            deflater: Deflater::new(level, Format::Gzip)?,
+            buffer: vec![0; 0x8000],
+            pos: 0,
+            len: 0,
+            finished: false,
+        })
+    }
+
+    // Same as `new`, with `header` in place of the bare gzip header.
+    pub fn with_header(
+        inner: W,
+        level: CompressionLevel,
+        header: &GzipHeader,
+    ) -> Result<AsyncGzEncoder<W>, ZlibError> {
+        let mut encoder = AsyncGzEncoder::new(inner, level)?;
+        encoder.deflater.set_header(header)?;
+        Ok(encoder)
+    }
+
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    // Writing to the inner writer directly corrupts the compressed output.
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
+
+    // Compressed bytes not written to the inner writer yet are lost, as is the
+    // trailer without a `shutdown` first.
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<W: AsyncWrite + Unpin> AsyncGzEncoder<W> {
+    // Writes out what's left in `buffer`, it's empty once this is ready.
+    fn poll_drain(&mut self, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        while self.pos < self.len {
+            let pending = &self.buffer[self.pos..self.len];
+            let written = ready!(Pin::new(&mut self.inner).poll_write(cx, pending))?;
+            if written == 0 {
+                return Poll::Ready(Err(std::io::ErrorKind::WriteZero.into()));
+            }
+            self.pos += written;
+        }
+        self.pos = 0;
+        self.len = 0;
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<W: AsyncWrite + Unpin> AsyncWrite for AsyncGzEncoder<W> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        let this = self.get_mut();
+        if buf.is_empty() {
+            return Poll::Ready(Ok(0));
+        }
+        loop {
+            ready!(this.poll_drain(cx))?;
+            let (consumed, produced) = this.deflater.compress(buf, &mut this.buffer)?;
+            this.len = produced;
+            // zlib only stops short of consuming anything when the output is full.
+            if consumed > 0 {
+                return Poll::Ready(Ok(consumed));
+            }
+            if produced == 0 {
+                return Poll::Ready(Err(ZlibError::BufError.into()));
+            }
+        }
+    }
+
+    // Pushes everything written so far to `inner` without ending the member.
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        let this = self.get_mut();
+        loop {
+            ready!(this.poll_drain(cx))?;
+            // Flushing again after a poll that had to wait is harmless, zlib
+            // carries on with the flush or finds there's nothing to do.
+            let (_, produced, _) = this.deflater.deflate(&[], &mut this.buffer, Z_SYNC_FLUSH)?;
+            this.len = produced;
+            // The flush is complete once zlib leaves room in the output.
+            if produced < this.buffer.len() {
+                break;
+            }
+        }
+        ready!(this.poll_drain(cx))?;
+        Pin::new(&mut this.inner).poll_flush(cx)
+    }
+
+    // Writes the trailer and shuts `inner` down. Writes fail after this.
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        let this = self.get_mut();
+        loop {
+            ready!(this.poll_drain(cx))?;
+            if this.finished {
+                break;
+            }
+            let (produced, done) = this.deflater.finish(&mut this.buffer)?;
+            this.len = produced;
+            this.finished = done;
+        }
+        Pin::new(&mut this.inner).poll_shutdown(cx)
+    }
+}
@@ -0,0 +1,165 @@
+use core::hash::Hasher;
+use core::ptr;
+
+use libc::{c_long, c_uint, c_ulong};
+
+#[cfg_attr(not(any(feature = "bundled", feature = "zlib-ng")), link(name = "z"))]
+unsafe extern "C" {
+    // ZEXTERN uLong ZEXPORT crc32 OF((uLong crc, const Bytef *buf, uInt len));
+    //
+    // ZEXTERN uLong ZEXPORT adler32 OF((uLong adler, const Bytef *buf, uInt len));
+    //
+    // ZEXTERN uLong ZEXPORT crc32_combine OF((uLong crc1, uLong crc2, z_off_t len2));
+    //
+    // ZEXTERN uLong ZEXPORT adler32_combine OF((uLong adler1, uLong adler2, z_off_t len2));
+
+    // Updates a running CRC-32 with the bytes in `buf`.
+    // Returns the required initial value when `buf` is null.
+    // Renamed on the Rust side, so the safe `crc32` below can take its name.
+    #[link_name = "crc32"]
+    unsafe fn c_crc32(crc: c_ulong, buf: *const u8, len: c_uint) -> c_ulong;
+
+    // Same as `crc32`, but for the Adler-32 used in zlib stream trailers.
+    #[link_name = "adler32"]
+    unsafe fn c_adler32(adler: c_ulong, buf: *const u8, len: c_uint) -> c_ulong;
+
+    // The CRC-32 of two pieces of data one after another, without the data itself.
+    #[link_name = "crc32_combine"]
+    unsafe fn c_crc32_combine(crc1: c_ulong, crc2: c_ulong, len2: c_long) -> c_ulong;
+
+    // Same as `crc32_combine`, but for Adler-32.
+    #[link_name = "adler32_combine"]
+    unsafe fn c_adler32_combine(adler1: c_ulong, adler2: c_ulong, len2: c_long) -> c_ulong;
+}
+
+// CRC-32 of `data`, the checksum used in gzip footers.
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = Crc32::new();
+    crc.update(data);
+    crc.finalize()
+}
+
+// Computes a CRC-32 of data that arrives in pieces, e.g. while streaming.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Crc32 {
+    value: u32,
+}
+
+impl Crc32 {
+    pub fn new() -> Crc32 {
+        let value = unsafe { c_crc32(0, ptr::null(), 0) };
+        Crc32 {
+            value: value as u32,
+        }
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        let mut crc = self.value as c_ulong;
+        // `len` is a C unsigned int, so feed huge slices piece by piece.
+        for chunk in data.chunks(c_uint::MAX as usize) {
+            crc = unsafe { c_crc32(crc, chunk.as_ptr(), chunk.len() as c_uint) };
+        }
+        self.value = crc as u32;
+    }
+
+    // The CRC-32 of everything passed to `update` so far.
+    pub fn finalize(self) -> u32 {
+        self.value
+    }
+}
+
+impl Default for Crc32 {
+    fn default() -> Crc32 {
+        Crc32::new()
+    }
+}
+
+// Lets a `Crc32` be used wherever a `Hasher` is expected.
+// Note that `Hash` implementations feed more than the raw bytes, e.g. a length
+// prefix for slices, so only `write` gives the CRC-32 of the data itself.
+impl Hasher for Crc32 {
+    fn finish(&self) -> u64 {
+        self.value as u64
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        self.update(bytes);
+    }
+}
+
+// Given `crc1` of some data A and `crc2` of some data B that is `len2` bytes long,
+// returns the CRC-32 of A followed by B. Handy for hashing chunks in parallel.
+pub fn crc32_combine(crc1: u32, crc2: u32, len2: u64) -> u32 {
+    // z_off_t is only 32 bits on some platforms. zlib shifts `crc1` by `len2`
+    // bytes and XORs `crc2` in, so shifting in steps with a `crc2` of 0 is the same.
+    let mut crc = crc1 as c_ulong;
+    let mut len2 = len2;
+    while len2 > c_long::MAX as u64 {
+        crc = unsafe { c_crc32_combine(crc, 0, c_long::MAX) };
+        len2 -= c_long::MAX as u64;
+    }
+    unsafe { c_crc32_combine(crc, crc2 as c_ulong, len2 as c_long) as u32 }
+}
+
+// Adler-32 of `data`, the checksum used in zlib stream trailers.
+pub fn adler32(data: &[u8]) -> u32 {
+    let mut adler = Adler32::new();
+    adler.update(data);
+    adler.finalize()
+}
+
+// Computes an Adler-32 of data that arrives in pieces,
+// e.g. to validate a zlib stream as it's being decompressed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Adler32 {
+    value: u32,
+}
+
+impl Adler32 {
+    pub fn new() -> Adler32 {
+        // The algorithm starts from 1, not 0.
+        Adler32 { value: 1 }
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        let mut adler = self.value as c_ulong;
+        // `len` is a C unsigned int, so feed huge slices piece by piece.
+        for chunk in data.chunks(c_uint::MAX as usize) {
+            adler = unsafe { c_adler32(adler, chunk.as_ptr(), chunk.len() as c_uint) };
+        }
+        self.value = adler as u32;
+    }
+
+    // The Adler-32 of everything passed to `update` so far.
+    pub fn finalize(self) -> u32 {
+        self.value
+    }
+}
+
+impl Default for Adler32 {
+    fn default() -> Adler32 {
+        Adler32::new()
+    }
+}
+
+// Same caveat as for `Crc32`: only `write` gives the Adler-32 of the data itself.
+impl Hasher for Adler32 {
+    fn finish(&self) -> u64 {
+        self.value as u64
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        self.update(bytes);
+    }
+}
+
+// The largest prime below 2^16, Adler-32 sums are taken modulo it.
+const ADLER32_BASE: u64 = 65521;
+
+// Given `a1` of some data A and `a2` of some data B that is `len2` bytes long,
+// returns the Adler-32 of A followed by B.
+pub fn adler32_combine(a1: u32, a2: u32, len2: u64) -> u32 {
+    // zlib only uses `len2` modulo the base, which also keeps it in a 32-bit z_off_t.
+    let len2 = (len2 % ADLER32_BASE) as c_long;
+    unsafe { c_adler32_combine(a1 as c_ulong, a2 as c_ulong, len2) as u32 }
+}
@@ -0,0 +1,315 @@
+use alloc::borrow::Cow;
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+
+use libc::{c_int, c_ulong};
+
+use crate::error::{Z_OK, ZlibError};
+
+// The link attribute tells `rustc` that we need to link these functions to zlib.
+//
+// This is equivalent to adding `-lz` flag at link time.
+// Instructs rustc that these functions belong to the external "z" library.
+//
+// Can verify this by running:
+// on macOS: `otool -L target/debug/zlib-wrapper`,
+// on Linux: `ldd`
+// on Windows: `dumpbin`
+//
+// With the `bundled` feature build.rs compiles zlib from ./zlib and links it
+// statically instead, there's no libz to find at run time. With `zlib-ng` it
+// links the libz in $ZLIB_NG_LIB_DIR.
+#[cfg_attr(not(any(feature = "bundled", feature = "zlib-ng")), link(name = "z"))]
+unsafe extern "C" {
+    // The biggest challenge is mapping verious types and functions from C to Rust + libc crate.
+    // The rust-bindgen tool can generate bindings to C libraries automatically from C headers.
+    //
+    // But sometimes dealing with rust-bindgen is not worth the trouble for simple cases.
+    // In those cases we can:
+    // 1. Copy the C struct definition.
+    // 2. Convert the C types to Rust types.
+    // 3. Implement function interfaaces.
+
+    // --------------------------------------------------------------------------
+    // Definitions from zlib.h
+    // --------------------------------------------------------------------------
+    //
+    // #ifndef ZEXTERN
+    // #  define ZEXTERN extern
+    // #endif
+    // #ifndef ZEXPORT
+    // #  define ZEXPORT
+    // #endif
+    //
+    // typedef unsigned char  Byte;  /* 8 bits */
+    // typedef unsigned int   uInt;  /* 16 bits or more */
+    // typedef unsigned long  uLong; /* 32 bits or more */
+    //
+    // typedef Byte  FAR Bytef;
+    // typedef char  FAR charf;
+    // typedef int   FAR intf;
+    // typedef uInt  FAR uIntf;
+    // typedef uLong FAR uLongf;
+    //
+    // ZEXTERN int ZEXPORT compress OF((Bytef *dest,   uLongf *destLen,
+    //                                  const Bytef *source, uLong sourceLen));
+    //
+    // ZEXTERN int ZEXPORT compress2 OF((Bytef *dest,   uLongf *destLen,
+    //                                   const Bytef *source, uLong sourceLen,
+    //                                   int level));
+    //
+    // ZEXTERN uLong ZEXPORT compressBound OF((uLong sourceLen));
+    //
+    // ZEXTERN int ZEXPORT uncompress OF((Bytef *dest,   uLongf *destLen,
+    //                                    const Bytef *source, uLong sourceLen));
+    // --------------------------------------------------------------------------
+
+    unsafe fn compress(
+        dest: *mut u8,
+        dest_len: *mut c_ulong,
+        source: *const u8,
+        source_len: c_ulong,
+    ) -> c_int;
+
+    unsafe fn compress2(
+        dest: *mut u8,
+        dest_len: *mut c_ulong,
+        source: *const u8,
+        source_len: c_ulong,
+        level: c_int,
+    ) -> c_int;
+
+    // Estimates the size of buffer required to
+    // compress `source_len` bytes of data using the compress()
+    unsafe fn compressBound(source_len: c_ulong) -> c_ulong;
+
+    unsafe fn uncompress(
+        dest: *mut u8,
+        dest_len: *mut c_ulong,
+        source: *const u8,
+        source_len: c_ulong,
+    ) -> c_int;
+}
+
+// The most bytes `zlib_compress` can produce from `source_len` bytes of input.
+// `usize::MAX` for a length longer than a `c_ulong` holds, which zlib can't take.
+pub fn compress_bound(source_len: usize) -> usize {
+    match c_ulong::try_from(source_len) {
+        Ok(source_len) => unsafe { compressBound(source_len) as usize },
+        Err(_) => usize::MAX,
+    }
+}
+
+// zlib takes lengths as a `c_ulong`, which is only 32 bits on 64-bit Windows.
+// An input longer than that fails with `StreamError` instead of being cut
+// short, a `Deflater` takes it piece by piece.
+fn ulong_len(source: &[u8]) -> Result<c_ulong, ZlibError> {
+    c_ulong::try_from(source.len()).map_err(|_| ZlibError::StreamError)
+}
+
+// The output buffers below are handed to C as uninitialized spare capacity.
+// Invariant: nothing reads that memory and `set_len` is called only after zlib
+// returned Z_OK, at which point exactly `dest_len` bytes have been written.
+// Miri can't check it, the bytes are written by zlib on the other side of the
+// FFI, which it doesn't execute. The round trips in tests/compress.rs cover it.
+//
+// Takes anything that can be borrowed as bytes, e.g. a `&str` or a `Vec<u8>`.
+// Fails with `StreamError` on input longer than a `c_ulong` holds, over 4 GiB
+// on 64-bit Windows.
+pub fn zlib_compress(source: impl AsRef<[u8]>) -> Result<Vec<u8>, ZlibError> {
+    let mut dest = Vec::new();
+    zlib_compress_into(source, &mut dest)?;
+    Ok(dest)
+}
+
+// Same as `zlib_compress`, but appends the output to `dest` instead of allocating.
+// Returns the number of bytes appended. In a loop compressing many small buffers,
+// `dest.clear()` and reuse it to keep its allocation.
+// It saves allocations rather than time: both are within noise of each other on
+// 200 byte messages and on 1 MB, see `cargo run --release --example bench
+// compress_into`. zlib's `compress` sets up and frees a whole deflate state per
+// call, which dwarfs the one output buffer.
+pub fn zlib_compress_into(
+    source: impl AsRef<[u8]>,
+    dest: &mut Vec<u8>,
+) -> Result<usize, ZlibError> {
+    let source = source.as_ref();
+    let source_len = ulong_len(source)?;
+    dest.reserve(compress_bound(source.len()));
+    unsafe {
+        // More room than a c_ulong can tell is left unused.
+        let spare = dest.spare_capacity_mut();
+        let mut dest_len = c_ulong::try_from(spare.len()).unwrap_or(c_ulong::MAX);
+
+        let code = compress(
+            spare.as_mut_ptr().cast::<u8>(),
+            &mut dest_len,
+            source.as_ptr(),
+            source_len,
+        );
+        if code != Z_OK {
+            return Err(ZlibError::from(code));
+        }
+
+        dest.set_len(dest.len() + dest_len as usize);
+        Ok(dest_len as usize)
+    }
+}
+
+// Same as `zlib_compress`, but gives back `source` itself, borrowed, when
+// compressing doesn't make it smaller, e.g. for already compressed or random data.
+// Storage formats do the same and keep a "stored" flag next to the data:
+// the caller has to record which case it got, a borrowed result is not a zlib
+// stream and `zlib_uncompress` fails on it.
+pub fn zlib_compress_cow(source: &[u8]) -> Result<Cow<'_, [u8]>, ZlibError> {
+    let compressed = zlib_compress(source)?;
+    if compressed.len() >= source.len() {
+        return Ok(Cow::Borrowed(source));
+    }
+    Ok(Cow::Owned(compressed))
+}
+
+// Compression levels, as defined in zlib.h
+//
+// #define Z_NO_COMPRESSION         0
+// #define Z_BEST_SPEED             1
+// #define Z_BEST_COMPRESSION       9
+// #define Z_DEFAULT_COMPRESSION  (-1)
+const Z_NO_COMPRESSION: c_int = 0;
+const Z_BEST_SPEED: c_int = 1;
+const Z_BEST_COMPRESSION: c_int = 9;
+pub(crate) const Z_DEFAULT_COMPRESSION: c_int = -1;
+
+// Trades speed for compression ratio.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompressionLevel {
+    // Let zlib pick, currently the same as `Level(6)`.
+    Default,
+    // Store the data as is, only wrapping it in the zlib format.
+    None,
+    // Best speed, same as `Level(1)`.
+    Fast,
+    // Best compression, same as `Level(9)`.
+    Best,
+    // An explicit level in the 0..=9 range.
+    Level(u8),
+}
+
+impl TryFrom<CompressionLevel> for c_int {
+    type Error = ZlibError;
+
+    // Fails with `StreamError` if a `Level` is above 9, just like zlib would.
+    fn try_from(level: CompressionLevel) -> Result<c_int, ZlibError> {
+        match level {
+            CompressionLevel::Default => Ok(Z_DEFAULT_COMPRESSION),
+            CompressionLevel::None => Ok(Z_NO_COMPRESSION),
+            CompressionLevel::Fast => Ok(Z_BEST_SPEED),
+            CompressionLevel::Best => Ok(Z_BEST_COMPRESSION),
+            CompressionLevel::Level(level) if level <= 9 => Ok(level as c_int),
+            CompressionLevel::Level(_) => Err(ZlibError::StreamError),
+        }
+    }
+}
+
+// Same as `zlib_compress`, but lets the caller trade speed for compression ratio.
+pub fn zlib_compress_level(
+    source: impl AsRef<[u8]>,
+    level: CompressionLevel,
+) -> Result<Vec<u8>, ZlibError> {
+    let source = source.as_ref();
+    let level = c_int::try_from(level)?;
+    let source_len = ulong_len(source)?;
+
+    unsafe {
+        let mut dest = Vec::<u8>::with_capacity(compress_bound(source.len()));
+        let mut dest_len = c_ulong::try_from(dest.capacity()).unwrap_or(c_ulong::MAX);
+
+        let code = compress2(
+            dest.spare_capacity_mut().as_mut_ptr().cast::<u8>(),
+            &mut dest_len,
+            source.as_ptr(),
+            source_len,
+            level,
+        );
+        if code != Z_OK {
+            return Err(ZlibError::from(code));
+        }
+
+        dest.set_len(dest_len as usize);
+        Ok(dest)
+    }
+}
+
+// Wraps `source` in a zlib stream of stored blocks without trying to compress
+// it, far faster than even `CompressionLevel::Fast` when the data is already
+// compressed. The output is a few bytes larger than `source` and still
+// decompresses with `zlib_uncompress`.
+pub fn zlib_store(source: &[u8]) -> Result<Vec<u8>, ZlibError> {
+    zlib_compress_level(source, CompressionLevel::None)
+}
+
+// Fails with `BufError` if `max_dest_len` is too small to hold the uncompressed data
+// and with `DataError` if the input is corrupted or incomplete. Like
+// `zlib_compress`, fails with `StreamError` if the input is too long for zlib.
+pub fn zlib_uncompress(source: &[u8], max_dest_len: usize) -> Result<Vec<u8>, ZlibError> {
+    let source_len = ulong_len(source)?;
+    unsafe {
+        // Always room for at least a byte: an empty Vec's pointer is dangling, and
+        // given no room at all zlib reports any non-empty stream as a `DataError`.
+        let capacity = max_dest_len.max(1);
+        let mut dest_len = c_ulong::try_from(capacity).unwrap_or(c_ulong::MAX);
+        let mut dest = Vec::<u8>::with_capacity(capacity);
+
+        let code = uncompress(
+            dest.spare_capacity_mut().as_mut_ptr().cast::<u8>(),
+            &mut dest_len,
+            source.as_ptr(),
+            source_len,
+        );
+        if code != Z_OK {
+            return Err(ZlibError::from(code));
+        }
+        if dest_len as usize > max_dest_len {
+            return Err(ZlibError::BufError);
+        }
+
+        dest.set_len(dest_len as usize);
+        Ok(dest)
+    }
+}
+
+// How many times `zlib_uncompress_auto` doubles its output buffer before giving up.
+// Starting from 4x the input, this allows expansion ratios of up to 4 * 2^16.
+const UNCOMPRESS_AUTO_MAX_RETRIES: u32 = 16;
+
+// Same as `zlib_uncompress`, but doesn't require knowing the uncompressed size.
+// Starts with a buffer 4 times the size of the input and doubles it every time
+// zlib reports there's not enough room.
+pub fn zlib_uncompress_auto(source: &[u8]) -> Result<Vec<u8>, ZlibError> {
+    zlib_uncompress_limited(source, None)
+}
+
+// Same as `zlib_uncompress_auto`, but fails with `LimitExceeded` instead of growing
+// the output past `max_output` bytes. Untrusted input should always have a limit,
+// a few KB of it can decompress to GBs.
+pub fn zlib_uncompress_limited(
+    source: &[u8],
+    max_output: Option<usize>,
+) -> Result<Vec<u8>, ZlibError> {
+    let max_output = max_output.unwrap_or(usize::MAX);
+    let mut max_dest_len = (4 * source.len()).max(64);
+    let mut retries = 0;
+    loop {
+        let dest_len = max_dest_len.min(max_output);
+        match zlib_uncompress(source, dest_len) {
+            Err(ZlibError::BufError) if dest_len == max_output => {
+                return Err(ZlibError::LimitExceeded);
+            }
+            Err(ZlibError::BufError) if retries < UNCOMPRESS_AUTO_MAX_RETRIES => {
+                max_dest_len *= 2;
+                retries += 1;
+            }
+            result => return result,
+        }
+    }
+}
@@ -0,0 +1,650 @@
+use alloc::sync::Arc;
+#[cfg(not(feature = "std"))]
+use alloc::{boxed::Box, vec, vec::Vec};
+use core::ptr;
+
+use libc::{c_char, c_int, c_uint, c_ulong};
+
+use crate::compress::CompressionLevel;
+use crate::error::{Z_BUF_ERROR, Z_OK, Z_STREAM_END, ZlibError};
+use crate::header::{GzHeader, GzipHeader, HeaderSlot};
+use crate::stream::{Allocator, Format, SharedAllocator, ZStream};
+use crate::version::ZLIB_VERSION;
+
+// Deflate parameters, as defined in zlib.h, zconf.h and zutil.h
+//
+// #define Z_NO_FLUSH      0
+// #define Z_SYNC_FLUSH    2
+// #define Z_FULL_FLUSH    3
+// #define Z_FINISH        4
+// #define Z_FILTERED            1
+// #define Z_HUFFMAN_ONLY        2
+// #define Z_RLE                 3
+// #define Z_FIXED               4
+// #define Z_DEFAULT_STRATEGY    0
+// #define Z_DEFLATED   8
+// #define MAX_WBITS   15 /* 32K LZ77 window */
+// #define MAX_MEM_LEVEL 9
+// #define DEF_MEM_LEVEL 8
+pub(crate) const Z_NO_FLUSH: c_int = 0;
+pub(crate) const Z_SYNC_FLUSH: c_int = 2;
+const Z_FULL_FLUSH: c_int = 3;
+const Z_FINISH: c_int = 4;
+const Z_FILTERED: c_int = 1;
+const Z_HUFFMAN_ONLY: c_int = 2;
+const Z_RLE: c_int = 3;
+const Z_FIXED: c_int = 4;
+const Z_DEFAULT_STRATEGY: c_int = 0;
+const Z_DEFLATED: c_int = 8;
+pub(crate) const MAX_WBITS: c_int = 15;
+const MAX_MEM_LEVEL: u8 = 9;
+const DEF_MEM_LEVEL: u8 = 8;
+
+// How much of the buffered compressed data to push out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FlushMode {
+    // Let zlib decide how much data to accumulate before producing output.
+    NoFlush,
+    // Emit everything compressed so far, aligned to a byte boundary,
+    // so the reader can decompress it without waiting for the rest of the stream.
+    SyncFlush,
+    // Same as `SyncFlush`, but also reset the compression state, so decompression
+    // can restart from this point. Degrades compression if used too often.
+    FullFlush,
+    // Emit everything and end the stream.
+    Finish,
+}
+
+// Tunes the deflate algorithm for a particular kind of data.
+// Affects only the compression ratio, any strategy decompresses the same way.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Strategy {
+    // Normal data.
+    Default,
+    // Data produced by a filter or predictor, mostly small values somewhat randomly distributed.
+    Filtered,
+    // No string matching, only Huffman coding. Good for data that was already LZ-compressed.
+    HuffmanOnly,
+    // Only look for runs of the same byte. Good for PNG-style image data.
+    Rle,
+    // No dynamic Huffman codes, for simpler decoders.
+    Fixed,
+}
+
+impl From<Strategy> for c_int {
+    fn from(strategy: Strategy) -> c_int {
+        match strategy {
+            Strategy::Default => Z_DEFAULT_STRATEGY,
+            Strategy::Filtered => Z_FILTERED,
+            Strategy::HuffmanOnly => Z_HUFFMAN_ONLY,
+            Strategy::Rle => Z_RLE,
+            Strategy::Fixed => Z_FIXED,
+        }
+    }
+}
+
+// Less common `Deflater` settings, `DeflateOptions::default()` is what `Deflater::new` uses.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DeflateOptions {
+    // How much memory the compression state takes, from 1 to 9. 1 uses the least
+    // memory but is slow and compresses worse, 9 is the fastest. The default is 8.
+    pub mem_level: u8,
+    pub strategy: Strategy,
+}
+
+impl Default for DeflateOptions {
+    fn default() -> DeflateOptions {
+        DeflateOptions {
+            mem_level: DEF_MEM_LEVEL,
+            strategy: Strategy::Default,
+        }
+    }
+}
+
+impl From<FlushMode> for c_int {
+    fn from(mode: FlushMode) -> c_int {
+        match mode {
+            FlushMode::NoFlush => Z_NO_FLUSH,
+            FlushMode::SyncFlush => Z_SYNC_FLUSH,
+            FlushMode::FullFlush => Z_FULL_FLUSH,
+            FlushMode::Finish => Z_FINISH,
+        }
+    }
+}
+
+#[cfg_attr(not(any(feature = "bundled", feature = "zlib-ng")), link(name = "z"))]
+unsafe extern "C" {
+    // typedef voidpf (*alloc_func) OF((voidpf opaque, uInt items, uInt size));
+    // typedef void   (*free_func)  OF((voidpf opaque, voidpf address));
+    //
+    // typedef struct z_stream_s {
+    //     z_const Bytef *next_in;     /* next input byte */
+    //     uInt     avail_in;  /* number of bytes available at next_in */
+    //     uLong    total_in;  /* total number of input bytes read so far */
+    //
+    //     Bytef    *next_out; /* next output byte will go here */
+    //     uInt     avail_out; /* remaining free space at next_out */
+    //     uLong    total_out; /* total number of bytes output so far */
+    //
+    //     z_const char *msg;  /* last error message, NULL if no error */
+    //     struct internal_state FAR *state; /* not visible by applications */
+    //
+    //     alloc_func zalloc;  /* used to allocate the internal state */
+    //     free_func  zfree;   /* used to free the internal state */
+    //     voidpf     opaque;  /* private data object passed to zalloc and zfree */
+    //
+    //     int     data_type;  /* best guess about the data type: binary or text
+    //                            for deflate, or the decoding state for inflate */
+    //     uLong   adler;      /* Adler-32 or CRC-32 value of the uncompressed data */
+    //     uLong   reserved;   /* reserved for future use */
+    // } z_stream;
+    //
+    // typedef z_stream FAR *z_streamp;
+    //
+    // ZEXTERN int ZEXPORT deflateInit2_ OF((z_streamp strm, int  level, int  method,
+    //                                       int windowBits, int memLevel,
+    //                                       int strategy, const char *version,
+    //                                       int stream_size));
+    // ZEXTERN int ZEXPORT deflate OF((z_streamp strm, int flush));
+    // ZEXTERN int ZEXPORT deflateEnd OF((z_streamp strm));
+    // ZEXTERN int ZEXPORT deflateReset OF((z_streamp strm));
+    // ZEXTERN uLong ZEXPORT deflateBound OF((z_streamp strm, uLong sourceLen));
+    // ZEXTERN int ZEXPORT deflateSetDictionary OF((z_streamp strm,
+    //                                              const Bytef *dictionary,
+    //                                              uInt  dictLength));
+    // ZEXTERN int ZEXPORT deflateSetHeader OF((z_streamp strm,
+    //                                          gz_headerp head));
+    // ZEXTERN int ZEXPORT deflateParams OF((z_streamp strm,
+    //                                       int level,
+    //                                       int strategy));
+    // ZEXTERN int ZEXPORT deflatePending OF((z_streamp strm,
+    //                                        unsigned *pending,
+    //                                        int *bits));
+    // ZEXTERN int ZEXPORT deflatePrime OF((z_streamp strm,
+    //                                      int bits,
+    //                                      int value));
+    // ZEXTERN int ZEXPORT deflateCopy OF((z_streamp dest,
+    //                                     z_streamp source));
+    // ZEXTERN int ZEXPORT deflateTune OF((z_streamp strm,
+    //                                     int good_length,
+    //                                     int max_lazy,
+    //                                     int nice_length,
+    //                                     int max_chain));
+    //
+    // deflateInit2 is a macro passing ZLIB_VERSION and sizeof(z_stream) to deflateInit2_.
+
+    unsafe fn deflateInit2_(
+        strm: *mut ZStream,
+        level: c_int,
+        method: c_int,
+        window_bits: c_int,
+        mem_level: c_int,
+        strategy: c_int,
+        version: *const c_char,
+        stream_size: c_int,
+    ) -> c_int;
+    unsafe fn deflate(strm: *mut ZStream, flush: c_int) -> c_int;
+    unsafe fn deflateEnd(strm: *mut ZStream) -> c_int;
+    unsafe fn deflateReset(strm: *mut ZStream) -> c_int;
+    unsafe fn deflateBound(strm: *mut ZStream, source_len: c_ulong) -> c_ulong;
+    unsafe fn deflateSetDictionary(
+        strm: *mut ZStream,
+        dictionary: *const u8,
+        dict_length: c_uint,
+    ) -> c_int;
+    unsafe fn deflateSetHeader(strm: *mut ZStream, head: *mut GzHeader) -> c_int;
+    unsafe fn deflateParams(strm: *mut ZStream, level: c_int, strategy: c_int) -> c_int;
+    unsafe fn deflatePending(strm: *mut ZStream, pending: *mut c_uint, bits: *mut c_int) -> c_int;
+    unsafe fn deflatePrime(strm: *mut ZStream, bits: c_int, value: c_int) -> c_int;
+    unsafe fn deflateCopy(dest: *mut ZStream, source: *mut ZStream) -> c_int;
+    unsafe fn deflateTune(
+        strm: *mut ZStream,
+        good_length: c_int,
+        max_lazy: c_int,
+        nice_length: c_int,
+        max_chain: c_int,
+    ) -> c_int;
+}
+
+// Compresses data piece by piece, so it never has to be in memory all at once.
+pub struct Deflater {
+    // zlib keeps a pointer back to the stream in its internal state and refuses
+    // to work with a stream that moved, so it has to live on the heap.
+    stream: Box<ZStream>,
+    // zlib only keeps a pointer to the header passed to `set_header`.
+    header: Option<Box<HeaderSlot>>,
+    // Since the stream started, zlib's own totals are only a `c_ulong`.
+    total_in: u64,
+    total_out: u64,
+    on_progress: Option<Box<dyn FnMut(u64, u64) + Send>>,
+    // Kept alive for the stream, `Drop` ends the stream before this goes.
+    allocator: Option<SharedAllocator>,
+}
+
+// Safe to move to another thread: the raw pointers in the stream only point
+// into memory the `Deflater` owns, its boxed state, header and buffers zlib
+// allocated, and the callbacks and allocator are `Send` themselves. zlib keeps
+// no thread-local state. It's not `Sync`, every call changes the stream, and
+// `&mut self` already rules out using one stream from two threads at once.
+unsafe impl Send for Deflater {}
+
+impl Deflater {
+    pub fn new(level: CompressionLevel, format: Format) -> Result<Deflater, ZlibError> {
+        Deflater::with_options(level, format, DeflateOptions::default())
+    }
+
+    // Same as `new`, tuned with `options`.
+    // Fails with `StreamError` if `options.mem_level` is out of range.
+    pub fn with_options(
+        level: CompressionLevel,
+        format: Format,
+        options: DeflateOptions,
+    ) -> Result<Deflater, ZlibError> {
+        Deflater::init(level, format, options, None)
+    }
+
+    // Same as `with_options`, with the internal state allocated by `allocator`.
+    pub fn with_allocator(
+        level: CompressionLevel,
+        format: Format,
+        options: DeflateOptions,
+        allocator: Box<dyn Allocator>,
+    ) -> Result<Deflater, ZlibError> {
+        Deflater::init(level, format, options, Some(Arc::new(allocator)))
+    }
+
+    fn init(
+        level: CompressionLevel,
+        format: Format,
+        options: DeflateOptions,
+        allocator: Option<SharedAllocator>,
+    ) -> Result<Deflater, ZlibError> {
+        let level = c_int::try_from(level)?;
+        if !(1..=MAX_MEM_LEVEL).contains(&options.mem_level) {
+            return Err(ZlibError::StreamError);
+        }
+        let mut stream = Box::new(ZStream::with_allocator(allocator.as_ref()));
+        let code = unsafe {
+            deflateInit2_(
+                &mut *stream,
+                level,
+                Z_DEFLATED,
+                format.window_bits(),
+                options.mem_level as c_int,
+                options.strategy.into(),
+                ZLIB_VERSION.as_ptr(),
+                size_of::<ZStream>() as c_int,
+            )
+        };
+        if code != Z_OK {
+            return Err(ZlibError::from(code));
+        }
+        Ok(Deflater {
+            stream,
+            header: None,
+            total_in: 0,
+            total_out: 0,
+            on_progress: None,
+            allocator,
+        })
+    }
+
+    // Calls `on_progress` with the total bytes consumed and produced since the
+    // stream started, after each call into zlib that compressed anything,
+    // e.g. to drive a progress bar. It's called from Rust once zlib returned,
+    // so a panic in it unwinds to the caller of the compressing method, which
+    // then never learns how much that call consumed and produced.
+    pub fn set_on_progress(&mut self, on_progress: Option<Box<dyn FnMut(u64, u64) + Send>>) {
+        self.on_progress = on_progress;
+    }
+
+    // Writes `header` instead of zlib's bare one, naming no file and with no mtime.
+    // Must be called before any input is compressed, it's kept across resets.
+    // Fails with `StreamError` for any format but `Gzip`, or if the name or
+    // comment contains a NUL byte.
+    pub fn set_header(&mut self, header: &GzipHeader) -> Result<(), ZlibError> {
+        let slot = self.header.insert(HeaderSlot::from_header(header)?);
+        let code = unsafe { deflateSetHeader(&mut *self.stream, &mut slot.raw) };
+        if code != Z_OK {
+            self.header = None;
+            return Err(ZlibError::from(code));
+        }
+        Ok(())
+    }
+
+    // Compresses as much of `input` into `output` as fits.
+    // Returns how many bytes were consumed from `input` and produced into `output`.
+    // zlib may buffer input internally, so producing nothing is not an error.
+    pub fn compress(
+        &mut self,
+        input: &[u8],
+        output: &mut [u8],
+    ) -> Result<(usize, usize), ZlibError> {
+        let (consumed, produced, _) = self.deflate(input, output, Z_NO_FLUSH)?;
+        Ok((consumed, produced))
+    }
+
+    // Starts a new stream with the same settings, keeping the allocated state.
+    // Cheaper than creating a new `Deflater` for every message: on 200 byte
+    // messages a reset and compress takes about a tenth of the time, most of a
+    // new one goes into allocating its 256 KB of state, see
+    // `cargo run --release --example bench reset`.
+    pub fn reset(&mut self) -> Result<(), ZlibError> {
+        let code = unsafe { deflateReset(&mut *self.stream) };
+        if code != Z_OK {
+            return Err(ZlibError::from(code));
+        }
+        self.total_in = 0;
+        self.total_out = 0;
+        Ok(())
+    }
+
+    // A copy of the stream in its current state, which carries on independently.
+    // E.g. compress a common prefix once, then copy the stream for every message
+    // that starts with it. The copy allocates through the same `Allocator`,
+    // but doesn't get the `on_progress` callback.
+    pub fn try_clone(&self) -> Result<Deflater, ZlibError> {
+        let mut stream = Box::new(ZStream::new());
+        // deflateCopy only reads the source.
+        let source = ptr::from_ref(&*self.stream).cast_mut();
+        let code = unsafe { deflateCopy(&mut *stream, source) };
+        if code != Z_OK {
+            return Err(ZlibError::from(code));
+        }
+        let mut clone = Deflater {
+            stream,
+            header: None,
+            total_in: self.total_in,
+            total_out: self.total_out,
+            on_progress: None,
+            allocator: self.allocator.clone(),
+        };
+        // The copy still points at this stream's header, which may go first.
+        if let Some(header) = &self.header {
+            let slot = clone.header.insert(header.duplicate());
+            let code = unsafe { deflateSetHeader(&mut *clone.stream, &mut slot.raw) };
+            if code != Z_OK {
+                return Err(ZlibError::from(code));
+            }
+        }
+        Ok(clone)
+    }
+
+    // Primes the compressor with data likely to appear in the input, which helps
+    // a lot with small, similar messages. The decompressing side needs the same
+    // dictionary. Must be called before any input is compressed, otherwise fails
+    // with `StreamError`, as it always does for `Format::Gzip`.
+    pub fn set_dictionary(&mut self, dict: &[u8]) -> Result<(), ZlibError> {
+        let dict_length = c_uint::try_from(dict.len()).map_err(|_| ZlibError::StreamError)?;
+        let code = unsafe { deflateSetDictionary(&mut *self.stream, dict.as_ptr(), dict_length) };
+        if code != Z_OK {
+            return Err(ZlibError::from(code));
+        }
+        Ok(())
+    }
+
+    // Changes the compression level and strategy for the input compressed from now on,
+    // same as `GzWriter::set_params`. zlib may have to compress what it buffered
+    // with the old settings first, into `output`. Returns how many bytes it produced.
+    // Fails with `BufError` if `output` was too small, call it again with more room.
+    pub fn set_params(
+        &mut self,
+        level: CompressionLevel,
+        strategy: Strategy,
+        output: &mut [u8],
+    ) -> Result<usize, ZlibError> {
+        let level = c_int::try_from(level)?;
+        let (_, avail_out) = self.stream.set_buffers(&[], output);
+        let code = unsafe { deflateParams(&mut *self.stream, level, strategy.into()) };
+        let produced = (avail_out - self.stream.avail_out) as usize;
+        self.stream.clear_buffers();
+        self.progress(0, produced);
+        if code != Z_OK {
+            return Err(ZlibError::from(code));
+        }
+        Ok(produced)
+    }
+
+    // The most bytes compressing `source_len` bytes and finishing can produce
+    // with this stream's settings. An `output` buffer this large lets
+    // `compress` followed by `finish` complete in one call each. `usize::MAX`
+    // for a length longer than a `c_ulong` holds, like `compress_bound`.
+    pub fn bound(&self, source_len: usize) -> usize {
+        let Ok(source_len) = c_ulong::try_from(source_len) else {
+            return usize::MAX;
+        };
+        // deflateBound only reads the stream.
+        let stream = ptr::from_ref(&*self.stream).cast_mut();
+        unsafe { deflateBound(stream, source_len) as usize }
+    }
+
+    // How much compressed output zlib holds that didn't fit the output so far:
+    // whole bytes, and bits (0 to 7) of a byte not yet complete. Input zlib
+    // buffered but hasn't compressed yet isn't counted, flush to include it.
+    pub fn pending(&self) -> Result<(u32, i32), ZlibError> {
+        let mut pending: c_uint = 0;
+        let mut bits: c_int = 0;
+        // deflatePending only reads the stream.
+        let stream = ptr::from_ref(&*self.stream).cast_mut();
+        let code = unsafe { deflatePending(stream, &mut pending, &mut bits) };
+        if code != Z_OK {
+            return Err(ZlibError::from(code));
+        }
+        Ok((pending, bits))
+    }
+
+    // Overrides the match finding parameters the compression level picked,
+    // see `configuration_table` in zlib's deflate.c for what they mean and
+    // the values of each level. For squeezing the last bytes out of a specific
+    // kind of data, `CompressionLevel` is the right knob otherwise.
+    // Call it right after creating or resetting the `Deflater`, before any input,
+    // as `set_params` and changing the level go back to the level's values.
+    pub fn tune(
+        &mut self,
+        good_length: i32,
+        max_lazy: i32,
+        nice_length: i32,
+        max_chain: i32,
+    ) -> Result<(), ZlibError> {
+        let code = unsafe {
+            deflateTune(
+                &mut *self.stream,
+                good_length,
+                max_lazy,
+                nice_length,
+                max_chain,
+            )
+        };
+        if code != Z_OK {
+            return Err(ZlibError::from(code));
+        }
+        Ok(())
+    }
+
+    // Inserts the low `bits` bits of `value`, up to 16, into the output ahead of
+    // the compressed data, e.g. the leftover bits of a previous stream being
+    // appended to. `Format::Raw` only, and only before anything was compressed
+    // since the stream started or was reset. Fails with `StreamError` if `bits`
+    // is out of range and with `BufError` if zlib has no room left for them.
+    pub fn prime(&mut self, bits: i32, value: i32) -> Result<(), ZlibError> {
+        if !(0..=16).contains(&bits) {
+            return Err(ZlibError::StreamError);
+        }
+        let code = unsafe { deflatePrime(&mut *self.stream, bits, value) };
+        if code != Z_OK {
+            return Err(ZlibError::from(code));
+        }
+        Ok(())
+    }
+
+    // Flushes everything zlib buffered and writes the stream trailer into `output`.
+    // Returns how many bytes were produced and whether the stream is complete.
+    // Call it again with a fresh `output` until it returns `true`.
+    pub fn finish(&mut self, output: &mut [u8]) -> Result<(usize, bool), ZlibError> {
+        let (_, produced, code) = self.deflate(&[], output, Z_FINISH)?;
+        Ok((produced, code == Z_STREAM_END))
+    }
+
+    pub(crate) fn deflate(
+        &mut self,
+        input: &[u8],
+        output: &mut [u8],
+        flush: c_int,
+    ) -> Result<(usize, usize, c_int), ZlibError> {
+        let (avail_in, avail_out) = self.stream.set_buffers(input, output);
+        let code = unsafe { deflate(&mut *self.stream, flush) };
+        let consumed = (avail_in - self.stream.avail_in) as usize;
+        let produced = (avail_out - self.stream.avail_out) as usize;
+        self.stream.clear_buffers();
+        self.progress(consumed, produced);
+
+        match code {
+            // Z_BUF_ERROR only means no progress was possible with these buffers.
+            Z_OK | Z_STREAM_END | Z_BUF_ERROR => Ok((consumed, produced, code)),
+            _ => Err(ZlibError::from(code)),
+        }
+    }
+
+    fn progress(&mut self, consumed: usize, produced: usize) {
+        if consumed == 0 && produced == 0 {
+            return;
+        }
+        self.total_in += consumed as u64;
+        self.total_out += produced as u64;
+        if let Some(on_progress) = &mut self.on_progress {
+            on_progress(self.total_in, self.total_out);
+        }
+    }
+
+    // The checksum of the data consumed so far: CRC-32 for `Format::Gzip`,
+    // Adler-32 for `Format::Zlib`, what `crc32` or `adler32` return for it.
+    // Always 1 for raw streams, which carry no checksum. zlib-ng only has the
+    // CRC-32 of a gzip stream once it's finished.
+    pub fn checksum(&self) -> u32 {
+        self.stream.adler as u32
+    }
+
+    // How many bytes were consumed since the stream started.
+    pub fn total_in(&self) -> u64 {
+        self.total_in
+    }
+
+    // How many compressed bytes were produced since the stream started.
+    pub fn total_out(&self) -> u64 {
+        self.total_out
+    }
+}
+
+impl Drop for Deflater {
+    fn drop(&mut self) {
+        unsafe {
+            deflateEnd(&mut *self.stream);
+        }
+    }
+}
+
+// Compresses `source` into a complete gzip member in memory, no file needed.
+pub fn gzip_compress(
+    source: impl AsRef<[u8]>,
+    level: CompressionLevel,
+) -> Result<Vec<u8>, ZlibError> {
+    deflate_to_vec(Deflater::new(level, Format::Gzip)?, source.as_ref())
+}
+
+// Same as `gzip_compress`, with `header` in place of the bare gzip header.
+pub fn gzip_compress_with_header(
+    source: impl AsRef<[u8]>,
+    level: CompressionLevel,
+    header: &GzipHeader,
+) -> Result<Vec<u8>, ZlibError> {
+    let mut deflater = Deflater::new(level, Format::Gzip)?;
+    deflater.set_header(header)?;
+    deflate_to_vec(deflater, source.as_ref())
+}
+
+// How much of the input each gzip member of `compress_parallel` holds. Every
+// member starts without a history to refer back to, at 1 MB that costs well
+// under 1% of compression.
+#[cfg(feature = "std")]
+const PARALLEL_BLOCK: usize = 0x100000;
+
+// Same as `gzip_compress`, but splits `source` into 1 MB blocks and compresses
+// them on `threads` threads at once, like pigz. Each block is a gzip member of
+// its own, one after another they're a valid gzip file, which gzip(1),
+// `gzip_decompress`, `GzReader`, `MultiGzDecoder` and `decompress_stream`
+// decompress into the whole of `source`. `GzDecoder` and `AsyncGzDecoder` read
+// a single member, past 1 MB they only give back the first block, use
+// `MultiGzDecoder` and `AsyncMultiGzDecoder` instead. The output is a little
+// larger than `gzip_compress`'s and differs from it. Fails with `StreamError`
+// if `threads` is 0.
+#[cfg(feature = "std")]
+pub fn compress_parallel(
+    source: &[u8],
+    level: CompressionLevel,
+    threads: usize,
+) -> Result<Vec<u8>, ZlibError> {
+    if threads == 0 {
+        return Err(ZlibError::StreamError);
+    }
+    // At least one member, an empty gzip file isn't a valid one.
+    if source.len() <= PARALLEL_BLOCK {
+        return gzip_compress(source, level);
+    }
+    let blocks: Vec<&[u8]> = source.chunks(PARALLEL_BLOCK).collect();
+    let next = core::sync::atomic::AtomicUsize::new(0);
+    let mut members: Vec<Option<Vec<u8>>> = vec![None; blocks.len()];
+    std::thread::scope(|scope| {
+        let workers: Vec<_> = (0..threads.min(blocks.len()))
+            .map(|_| {
+                scope.spawn(|| {
+                    // Each takes the next block still to do until there are none left.
+                    let mut done = Vec::new();
+                    loop {
+                        let index = next.fetch_add(1, core::sync::atomic::Ordering::Relaxed);
+                        let Some(block) = blocks.get(index) else {
+                            return Ok(done);
+                        };
+                        done.push((index, gzip_compress(block, level)?));
+                    }
+                })
+            })
+            .collect();
+        for worker in workers {
+            let done: Result<Vec<(usize, Vec<u8>)>, ZlibError> = worker.join().unwrap();
+            for (index, member) in done? {
+                members[index] = Some(member);
+            }
+        }
+        Ok::<(), ZlibError>(())
+    })?;
+    Ok(members.into_iter().flatten().flatten().collect())
+}
+
+fn deflate_to_vec(mut deflater: Deflater, source: &[u8]) -> Result<Vec<u8>, ZlibError> {
+    let mut dest = vec![0u8; deflater.bound(source.len())];
+    let mut produced = 0;
+
+    // zlib takes at most 4 GB of input per call.
+    let mut input = source;
+    while !input.is_empty() {
+        let (consumed, written) = deflater.compress(input, &mut dest[produced..])?;
+        if consumed == 0 && written == 0 {
+            return Err(ZlibError::BufError);
+        }
+        input = &input[consumed..];
+        produced += written;
+    }
+    loop {
+        let (written, done) = deflater.finish(&mut dest[produced..])?;
+        produced += written;
+        if done {
+            break;
+        }
+        if written == 0 {
+            return Err(ZlibError::BufError);
+        }
+    }
+
+    dest.truncate(produced);
+    Ok(dest)
+}
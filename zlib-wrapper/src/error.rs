@@ -0,0 +1,125 @@
+use libc::c_int;
+
+// Return codes of the zlib functions, as defined in zlib.h
+//
+// #define Z_OK            0
+// #define Z_STREAM_END    1
+// #define Z_NEED_DICT     2
+// #define Z_ERRNO        (-1)
+// #define Z_STREAM_ERROR (-2)
+// #define Z_DATA_ERROR   (-3)
+// #define Z_MEM_ERROR    (-4)
+// #define Z_BUF_ERROR    (-5)
+// #define Z_VERSION_ERROR (-6)
+pub(crate) const Z_OK: c_int = 0;
+pub(crate) const Z_STREAM_END: c_int = 1;
+const Z_NEED_DICT: c_int = 2;
+pub(crate) const Z_ERRNO: c_int = -1;
+pub(crate) const Z_STREAM_ERROR: c_int = -2;
+const Z_DATA_ERROR: c_int = -3;
+const Z_MEM_ERROR: c_int = -4;
+pub(crate) const Z_BUF_ERROR: c_int = -5;
+const Z_VERSION_ERROR: c_int = -6;
+
+// Non-Z_OK return codes of the zlib functions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ZlibError {
+    // The end of the compressed stream was reached.
+    StreamEnd,
+    // A preset dictionary is needed to continue decompression.
+    NeedDict,
+    // A file operation failed, carries the `errno` value.
+    Errno(i32),
+    // The stream state was inconsistent or a parameter was invalid.
+    StreamError,
+    // The input data was corrupted or incomplete.
+    DataError,
+    // Not enough memory.
+    MemError,
+    // Not enough room in the output buffer.
+    BufError,
+    // The linked zlib library is incompatible with the zlib.h we're mirroring.
+    VersionError,
+    // The decompressed data grew past the limit set for it, e.g. a decompression bomb.
+    LimitExceeded,
+}
+
+impl From<c_int> for ZlibError {
+    fn from(code: c_int) -> ZlibError {
+        match code {
+            Z_STREAM_END => ZlibError::StreamEnd,
+            Z_NEED_DICT => ZlibError::NeedDict,
+            #[cfg(feature = "std")]
+            Z_ERRNO => {
+                let errno = std::io::Error::last_os_error().raw_os_error();
+                ZlibError::Errno(errno.unwrap_or(0))
+            }
+            // There's no errno to ask for without an operating system.
+            #[cfg(not(feature = "std"))]
+            Z_ERRNO => ZlibError::Errno(0),
+            Z_STREAM_ERROR => ZlibError::StreamError,
+            Z_DATA_ERROR => ZlibError::DataError,
+            Z_MEM_ERROR => ZlibError::MemError,
+            Z_BUF_ERROR => ZlibError::BufError,
+            Z_VERSION_ERROR => ZlibError::VersionError,
+            // Not a code zlib.h defines, treat it as misuse of the stream.
+            _ => ZlibError::StreamError,
+        }
+    }
+}
+
+impl core::fmt::Display for ZlibError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            ZlibError::StreamEnd => write!(f, "end of stream"),
+            ZlibError::NeedDict => write!(f, "need dictionary"),
+            #[cfg(feature = "std")]
+            ZlibError::Errno(errno) => {
+                write!(f, "{}", std::io::Error::from_raw_os_error(*errno))
+            }
+            #[cfg(not(feature = "std"))]
+            ZlibError::Errno(errno) => write!(f, "errno {}", errno),
+            ZlibError::StreamError => write!(f, "stream error"),
+            ZlibError::DataError => write!(f, "data error"),
+            ZlibError::MemError => write!(f, "insufficient memory"),
+            ZlibError::BufError => write!(f, "buffer error"),
+            ZlibError::VersionError => write!(f, "incompatible version"),
+            ZlibError::LimitExceeded => write!(f, "output size limit exceeded"),
+        }
+    }
+}
+
+impl core::error::Error for ZlibError {}
+
+#[cfg(feature = "std")]
+impl From<ZlibError> for std::io::Error {
+    fn from(err: ZlibError) -> std::io::Error {
+        match err {
+            ZlibError::Errno(errno) => std::io::Error::from_raw_os_error(errno),
+            _ => std::io::Error::other(err),
+        }
+    }
+}
+
+// For the helpers doing file I/O themselves. A `ZlibError` that went through
+// `std::io::Error`, e.g. from a `GzEncoder`, comes back as it was.
+#[cfg(feature = "std")]
+impl From<std::io::Error> for ZlibError {
+    fn from(err: std::io::Error) -> ZlibError {
+        if let Some(errno) = err.raw_os_error() {
+            return ZlibError::Errno(errno);
+        }
+        if let Some(err) = err
+            .get_ref()
+            .and_then(|err| err.downcast_ref::<ZlibError>())
+        {
+            return *err;
+        }
+        match err.kind() {
+            std::io::ErrorKind::UnexpectedEof | std::io::ErrorKind::InvalidData => {
+                ZlibError::DataError
+            }
+            _ => ZlibError::Errno(libc::EIO),
+        }
+    }
+}
@@ -0,0 +1,806 @@
+use alloc::ffi::CString;
+use core::ffi::CStr;
+use core::marker::{PhantomData, PhantomPinned};
+#[cfg(unix)]
+use std::os::unix::io::RawFd;
+use std::path::Path;
+
+use libc::{SEEK_CUR, SEEK_SET, c_char, c_int, c_uchar, c_uint, c_void, size_t};
+
+use crate::compress::{CompressionLevel, Z_DEFAULT_COMPRESSION};
+use crate::deflate::{FlushMode, Strategy};
+use crate::error::{Z_BUF_ERROR, Z_ERRNO, Z_OK, Z_STREAM_ERROR, ZlibError};
+
+// zlib's file state, only ever handled through a pointer. zlib.h exposes the
+// first few fields for its gzgetc macro, but their layout is an implementation
+// detail: z_off64_t isn't always 64 bits, and other zlibs, zlib-ng among them,
+// are free to lay it out differently. So it's opaque here, the position comes
+// from gztell through `tell`. Not `Send`, `Sync` or `Unpin`, like the C struct.
+#[repr(C)]
+pub struct GzFileState {
+    _opaque: [u8; 0],
+    _marker: PhantomData<(*mut u8, PhantomPinned)>,
+}
+
+pub type GzFile = *mut GzFileState;
+
+// Instructs rustc that these functions belong to the external "z" library.
+// Yes, "z" in the name of the "zlib" library.
+#[cfg_attr(not(any(feature = "bundled", feature = "zlib-ng")), link(name = "z"))]
+unsafe extern "C" {
+    // typedef struct gzFile_s *gzFile;
+    //
+    // ZEXTERN gzFile ZEXPORT gzopen OF((const char *, const char *));
+    // ZEXTERN gzFile ZEXPORT gzdopen OF((int fd, const char *mode));
+    // ZEXTERN int ZEXPORT gzread OF((gzFile file, voidp buf, unsigned len));
+    // ZEXTERN int ZEXPORT gzclose OF((gzFile file));
+    // ZEXTERN int ZEXPORT gzeof OF((gzFile file));
+    // ZEXTERN int ZEXPORT gzdirect OF((gzFile file));
+    // ZEXTERN int ZEXPORT gzwrite OF((gzFile file, voidpc buf, unsigned len));
+    // ZEXTERN int ZEXPORT gzflush OF((gzFile file, int flush));
+    //
+    // z_off_t is a long, as defined in zconf.h
+    //
+    // ZEXTERN z_off_t ZEXPORT gzseek OF((gzFile file, z_off_t offset, int whence));
+    // ZEXTERN z_off_t ZEXPORT gztell OF((gzFile file));
+    // ZEXTERN z_off_t ZEXPORT gzoffset OF((gzFile file));
+    // ZEXTERN int ZEXPORT gzrewind OF((gzFile file));
+    // ZEXTERN char * ZEXPORT gzgets OF((gzFile file, char *buf, int len));
+    // ZEXTERN int ZEXPORT gzputs OF((gzFile file, const char *s));
+    // ZEXTERN int ZEXPORT gzgetc OF((gzFile file));
+    // ZEXTERN int ZEXPORT gzputc OF((gzFile file, int c));
+    // ZEXTERN int ZEXPORT gzungetc OF((int c, gzFile file));
+    // ZEXTERN z_size_t ZEXPORT gzfread OF((voidp buf, z_size_t size, z_size_t nitems,
+    //                                      gzFile file));
+    // ZEXTERN z_size_t ZEXPORT gzfwrite OF((voidpc buf, z_size_t size,
+    //                                       z_size_t nitems, gzFile file));
+    //
+    // z_size_t is a size_t when the library has one, as any platform Rust runs on does.
+    // ZEXTERN int ZEXPORT gzbuffer OF((gzFile file, unsigned size));
+    // ZEXTERN int ZEXPORT gzsetparams OF((gzFile file, int level, int strategy));
+    // ZEXTERN const char * ZEXPORT gzerror OF((gzFile file, int *errnum));
+    // ZEXTERN void ZEXPORT gzclearerr OF((gzFile file));
+
+    unsafe fn gzopen(path: *const c_char, mode: *const c_char) -> GzFile;
+    unsafe fn gzdopen(fd: c_int, mode: *const c_char) -> GzFile;
+    unsafe fn gzread(file: GzFile, buf: *mut c_uchar, len: c_uint) -> c_int;
+    unsafe fn gzclose(file: GzFile) -> c_int;
+    unsafe fn gzeof(file: GzFile) -> c_int;
+    unsafe fn gzdirect(file: GzFile) -> c_int;
+    unsafe fn gzwrite(file: GzFile, buf: *const c_uchar, len: c_uint) -> c_int;
+    unsafe fn gzflush(file: GzFile, flush: c_int) -> c_int;
+    #[cfg(not(feature = "largefile"))]
+    unsafe fn gzseek(file: GzFile, offset: libc::c_long, whence: c_int) -> libc::c_long;
+    #[cfg(not(feature = "largefile"))]
+    unsafe fn gztell(file: GzFile) -> libc::c_long;
+    #[cfg(not(feature = "largefile"))]
+    unsafe fn gzoffset(file: GzFile) -> libc::c_long;
+    unsafe fn gzrewind(file: GzFile) -> c_int;
+    unsafe fn gzgets(file: GzFile, buf: *mut c_char, len: c_int) -> *mut c_char;
+    unsafe fn gzputs(file: GzFile, s: *const c_char) -> c_int;
+    // zlib.h also defines gzgetc as a macro reading straight from the buffer,
+    // the function does the same thing.
+    unsafe fn gzgetc(file: GzFile) -> c_int;
+    unsafe fn gzputc(file: GzFile, c: c_int) -> c_int;
+    unsafe fn gzungetc(c: c_int, file: GzFile) -> c_int;
+    unsafe fn gzfread(buf: *mut c_void, size: size_t, nitems: size_t, file: GzFile) -> size_t;
+    unsafe fn gzfwrite(buf: *const c_void, size: size_t, nitems: size_t, file: GzFile) -> size_t;
+    unsafe fn gzbuffer(file: GzFile, size: c_uint) -> c_int;
+    unsafe fn gzsetparams(file: GzFile, level: c_int, strategy: c_int) -> c_int;
+    unsafe fn gzerror(file: GzFile, errnum: *mut c_int) -> *const c_char;
+    unsafe fn gzclearerr(file: GzFile);
+}
+
+#[cfg(feature = "largefile")]
+#[cfg_attr(not(any(feature = "bundled", feature = "zlib-ng")), link(name = "z"))]
+unsafe extern "C" {
+    // Only declared when zlib is built with _LARGEFILE64_SOURCE.
+    //
+    // ZEXTERN z_off64_t ZEXPORT gzseek64 OF((gzFile, z_off64_t, int));
+    // ZEXTERN z_off64_t ZEXPORT gztell64 OF((gzFile));
+    // ZEXTERN z_off64_t ZEXPORT gzoffset64 OF((gzFile));
+
+    unsafe fn gzseek64(file: GzFile, offset: i64, whence: c_int) -> i64;
+    unsafe fn gztell64(file: GzFile) -> i64;
+    unsafe fn gzoffset64(file: GzFile) -> i64;
+}
+
+// z_off_t is a `long`, so the plain functions are limited to 2 GB where that's 32 bits.
+#[cfg(not(feature = "largefile"))]
+type ZOff = libc::c_long;
+#[cfg(feature = "largefile")]
+type ZOff = i64;
+
+fn gz_seek_off(file: GzFile, offset: ZOff, whence: c_int) -> ZOff {
+    #[cfg(not(feature = "largefile"))]
+    let position = unsafe { gzseek(file, offset, whence) };
+    #[cfg(feature = "largefile")]
+    let position = unsafe { gzseek64(file, offset, whence) };
+    position
+}
+
+fn gz_tell_off(file: GzFile) -> ZOff {
+    #[cfg(not(feature = "largefile"))]
+    let position = unsafe { gztell(file) };
+    #[cfg(feature = "largefile")]
+    let position = unsafe { gztell64(file) };
+    position
+}
+
+fn gz_offset_off(file: GzFile) -> ZOff {
+    #[cfg(not(feature = "largefile"))]
+    let offset = unsafe { gzoffset(file) };
+    #[cfg(feature = "largefile")]
+    let offset = unsafe { gzoffset64(file) };
+    offset
+}
+
+// How `gz_open` opens a file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GzMode {
+    // Read an existing file, gzipped or not.
+    Read,
+    // Create the file or truncate it if it exists.
+    Write(CompressionLevel),
+    // Add a new gzip member to the end of the file, creating it if needed.
+    Append(CompressionLevel),
+}
+
+impl GzMode {
+    // Builds the gzopen mode string, e.g. "rb" or "wb9".
+    fn to_c_string(self) -> Result<CString, ZlibError> {
+        let (mode, level) = match self {
+            GzMode::Read => ("rb", None),
+            GzMode::Write(level) => ("wb", Some(level)),
+            GzMode::Append(level) => ("ab", Some(level)),
+        };
+        let mut mode = mode.to_string();
+        if let Some(level) = level {
+            // Without a level suffix gzopen uses the default compression.
+            let level = c_int::try_from(level)?;
+            if level != Z_DEFAULT_COMPRESSION {
+                mode.push_str(&level.to_string());
+            }
+        }
+        Ok(CString::new(mode).expect("CString failed"))
+    }
+}
+
+// An open gz file, closed with gzclose when dropped,
+// so early returns and panics don't leak it.
+pub struct GzHandle {
+    file: GzFile,
+}
+
+impl GzHandle {
+    // Takes over a handle returned by gzopen or gzdopen, which must not be null.
+    fn from_raw(file: GzFile) -> GzHandle {
+        GzHandle { file }
+    }
+
+    // The raw handle for the gz* functions, only valid while `self` is alive.
+    pub fn as_ptr(&self) -> GzFile {
+        self.file
+    }
+
+    // Closes the file now and reports how that went, which dropping can't.
+    pub fn close(self) -> Result<(), ZlibError> {
+        let code = unsafe { gzclose(self.file) };
+        // Closed already, `drop` mustn't close it again.
+        std::mem::forget(self);
+        match code {
+            Z_OK => Ok(()),
+            Z_ERRNO => Err(std::io::Error::last_os_error().into()),
+            code => Err(ZlibError::from(code)),
+        }
+    }
+}
+
+impl Drop for GzHandle {
+    fn drop(&mut self) {
+        let code = unsafe { gzclose(self.file) };
+        // There's no one to report a failed close to from here, e.g. the last
+        // flush of a write handle not making it to disk. Z_STREAM_ERROR is
+        // only returned for a handle that isn't valid, that would be a bug here.
+        debug_assert_ne!(code, Z_STREAM_ERROR, "gzclose on an invalid handle");
+    }
+}
+
+// Opens a gz file, it's closed when the returned handle is dropped.
+pub fn gz_open(path: &str, mode: GzMode) -> Result<GzHandle, ZlibError> {
+    // Convert rust UTF-8 into an ASCII C-string.
+    // A path with an interior NUL byte can't be passed to C at all.
+    let c_path = CString::new(path).map_err(|_| ZlibError::StreamError)?;
+    let c_mode = mode.to_c_string()?;
+    let file = unsafe { gzopen(c_path.as_ptr(), c_mode.as_ptr()) };
+    if file.is_null() {
+        let errno = std::io::Error::last_os_error().raw_os_error();
+        return Err(ZlibError::Errno(errno.unwrap_or(0)));
+    }
+    Ok(GzHandle::from_raw(file))
+}
+
+// Same as `gz_open`, but for an already open file descriptor, e.g. a pipe or stdin.
+// The descriptor is owned by the returned handle from now on, gzclose closes it.
+#[cfg(unix)]
+fn gz_dopen(fd: RawFd, mode: GzMode) -> Result<GzHandle, ZlibError> {
+    let c_mode = mode.to_c_string()?;
+    let file = unsafe { gzdopen(fd, c_mode.as_ptr()) };
+    if file.is_null() {
+        let errno = std::io::Error::last_os_error().raw_os_error();
+        return Err(ZlibError::Errno(errno.unwrap_or(0)));
+    }
+    Ok(GzHandle::from_raw(file))
+}
+
+// Opens gzipped file, reads its contents, and returns them as raw bytes.
+pub fn read_gz_file_bytes(name: &str) -> Result<Vec<u8>, ZlibError> {
+    read_gz_file_bytes_with_capacity(name, READ_GZ_FILE_BUFFER)
+}
+
+// How much `read_gz_file` reads per gzread call. Inflating takes nearly all
+// the time, reading 112 MB of text is within noise at 4 KB, 256 KB and 1 MB,
+// see `cargo run --release --example bench read_buffer`.
+const READ_GZ_FILE_BUFFER: usize = 0x1000; // 16^3 = 4096 bytes
+
+fn read_gz_file_bytes_with_capacity(name: &str, buf_size: usize) -> Result<Vec<u8>, ZlibError> {
+    if buf_size == 0 {
+        return Err(ZlibError::StreamError);
+    }
+    let mut buffer = vec![0u8; buf_size.min(GZREAD_MAX)];
+    let mut contents = Vec::new();
+
+    let handle = gz_open(name, GzMode::Read)?;
+    let file = handle.as_ptr();
+    // gzeof only turns true after a read hit the end of the file,
+    // so read until there's nothing left and only then check it.
+    loop {
+        let bytes_read = unsafe { gzread(file, buffer.as_mut_ptr(), buffer.len() as c_uint) };
+        if bytes_read < 0 {
+            return Err(gz_error(file));
+        }
+        if bytes_read == 0 {
+            gz_end(file)?;
+            return Ok(contents);
+        }
+        contents.extend_from_slice(&buffer[..(bytes_read as usize)]);
+    }
+}
+
+// Opens gzipped file, reads its contents, and returns them as a string.
+// Fails with `DataError` if the contents are not valid UTF-8.
+pub fn read_gz_file(name: &str) -> Result<String, ZlibError> {
+    read_gz_file_with_capacity(name, READ_GZ_FILE_BUFFER)
+}
+
+// Same as `read_gz_file`, reading `buf_size` bytes per gzread call instead of
+// 4 KB, a few percent faster for large files with a buffer of 256 KB or more.
+// Fails with `StreamError` if `buf_size` is 0.
+pub fn read_gz_file_with_capacity(name: &str, buf_size: usize) -> Result<String, ZlibError> {
+    let contents = read_gz_file_bytes_with_capacity(name, buf_size)?;
+    String::from_utf8(contents).map_err(|_| ZlibError::DataError)
+}
+
+// A type `GzReader::read_items` and `GzWriter::write_items` can read and write
+// as raw bytes. Implemented for the integer and floating point types and arrays
+// of them.
+//
+// Safety: any bit pattern of the right size must be a valid value, and there
+// must be no padding bytes, e.g. a `#[repr(C)]` struct of `u32` fields. Not
+// `bool`, `char`, references or enums. The bytes are in the machine's own
+// order, so a file written on a big-endian machine reads back differently on
+// a little-endian one, convert with `to_le` and `from_le` to be portable.
+// The safety section is the paragraph above, clippy only reads `///` docs.
+#[allow(clippy::missing_safety_doc)]
+pub unsafe trait GzItem: Copy {}
+
+unsafe impl GzItem for u8 {}
+unsafe impl GzItem for u16 {}
+unsafe impl GzItem for u32 {}
+unsafe impl GzItem for u64 {}
+unsafe impl GzItem for u128 {}
+unsafe impl GzItem for usize {}
+unsafe impl GzItem for i8 {}
+unsafe impl GzItem for i16 {}
+unsafe impl GzItem for i32 {}
+unsafe impl GzItem for i64 {}
+unsafe impl GzItem for i128 {}
+unsafe impl GzItem for isize {}
+unsafe impl GzItem for f32 {}
+unsafe impl GzItem for f64 {}
+unsafe impl<T: GzItem, const N: usize> GzItem for [T; N] {}
+
+// Reads a gz file as a regular `std::io::Read`,
+// so it can be wrapped in a `BufReader`, read with `read_to_string`, etc.
+pub struct GzReader {
+    file: GzHandle,
+}
+
+impl GzReader {
+    pub fn open(name: &str) -> Result<GzReader, ZlibError> {
+        let file = gz_open(name, GzMode::Read)?;
+        Ok(GzReader { file })
+    }
+
+    // Reads from an already open file descriptor, e.g. stdin (0) or a socket.
+    // The descriptor is owned by the reader from now on and is closed when it's dropped.
+    // Fails with `StreamError` unless `mode` is `GzMode::Read`.
+    #[cfg(unix)]
+    pub fn from_raw_fd(fd: RawFd, mode: GzMode) -> Result<GzReader, ZlibError> {
+        if mode != GzMode::Read {
+            return Err(ZlibError::StreamError);
+        }
+        let file = gz_dopen(fd, mode)?;
+        Ok(GzReader { file })
+    }
+
+    // The position in the uncompressed data, i.e. how many decompressed bytes
+    // were read (or skipped by seeking) so far. This is not the offset in the
+    // file on disk, which is generally much smaller.
+    pub fn tell(&self) -> Result<u64, ZlibError> {
+        gz_tell(self.file.as_ptr())
+    }
+
+    // The position in the file on disk, i.e. how many compressed bytes zlib
+    // consumed so far, as opposed to `tell`'s position in the decompressed
+    // data. Input zlib read ahead into its buffer but didn't decompress yet
+    // isn't counted. Together with `tell` it maps decompressed offsets to
+    // compressed ones, e.g. to build an index for seeking.
+    pub fn compressed_offset(&self) -> Result<u64, ZlibError> {
+        gz_offset(self.file.as_ptr())
+    }
+
+    // Whether a read hit the end of the file.
+    // It only turns true after a read returned less than asked for, not when
+    // the last byte was read, so check it after a read returned 0.
+    pub fn eof(&self) -> bool {
+        unsafe { gzeof(self.file.as_ptr()) != 0 }
+    }
+
+    // Whether the file isn't gzipped and is read as is, zlib passes anything
+    // that doesn't start with a gzip header through unchanged. E.g. to warn
+    // about a plain file named `.gz`. Before the first read it has zlib read
+    // the start of the file to find out.
+    pub fn is_direct(&self) -> bool {
+        unsafe { gzdirect(self.file.as_ptr()) != 0 }
+    }
+
+    // The zlib error code and message of the last failed operation, if any,
+    // e.g. (-3, "file.gz: incorrect data check") for a corrupted file.
+    pub fn last_error(&self) -> Option<(i32, String)> {
+        gz_last_error(self.file.as_ptr())
+    }
+
+    // Forgets the last error, so the handle can be used again after a recoverable one.
+    pub fn clear_error(&mut self) {
+        unsafe { gzclearerr(self.file.as_ptr()) }
+    }
+
+    // Sets the size of zlib's internal buffers, 8 KB by default.
+    // Must be called before the first read, otherwise fails with `StreamError`.
+    pub fn set_buffer_size(&mut self, size: u32) -> Result<(), ZlibError> {
+        gz_buffer(self.file.as_ptr(), size)
+    }
+
+    // Reads the next decompressed byte, `None` at the end of the file or on an
+    // error, tell which with `last_error`. Cheaper than a one-byte `read`,
+    // it's served from zlib's buffer without going through `gzread`.
+    pub fn read_byte(&mut self) -> Option<u8> {
+        let c = unsafe { gzgetc(self.file.as_ptr()) };
+        u8::try_from(c).ok()
+    }
+
+    // Pushes `b` back, the next `read_byte` or `read` returns it first, e.g. to
+    // peek at a byte with `read_byte`. It doesn't have to be the byte that was
+    // read. Only one byte is guaranteed to fit before reading again, pushing
+    // more fails with `DataError` once zlib runs out of room. Seeking or
+    // rewinding drops what was pushed back.
+    pub fn unread_byte(&mut self, b: u8) -> Result<(), ZlibError> {
+        let c = unsafe { gzungetc(c_int::from(b), self.file.as_ptr()) };
+        if c < 0 {
+            return Err(gz_error(self.file.as_ptr()));
+        }
+        Ok(())
+    }
+
+    // Reads as many whole items into `buf` as there are, like C's fread.
+    // Returns how many were read, 0 at the end of the file. When the file ends
+    // in the middle of an item, its bytes are still read into `buf` past the
+    // count, but not counted, and `eof` turns true.
+    pub fn read_items<T: GzItem>(&mut self, buf: &mut [T]) -> Result<usize, ZlibError> {
+        if buf.is_empty() || size_of::<T>() == 0 {
+            return Ok(0);
+        }
+        let file = self.file.as_ptr();
+        let buf_ptr = buf.as_mut_ptr().cast::<c_void>();
+        let read = unsafe { gzfread(buf_ptr, size_of::<T>(), buf.len(), file) };
+        // Zero items is either the end of the file or an error.
+        if read == 0 && gz_last_error(file).is_some() {
+            return Err(gz_error(file));
+        }
+        Ok(read)
+    }
+
+    // Starts reading from the beginning of the file again, e.g. for a second pass.
+    pub fn rewind(&mut self) -> Result<(), ZlibError> {
+        let code = unsafe { gzrewind(self.file.as_ptr()) };
+        if code != Z_OK {
+            return Err(ZlibError::StreamError);
+        }
+        Ok(())
+    }
+}
+
+// The error of the last failed operation on `file`.
+fn gz_error(file: GzFile) -> ZlibError {
+    let mut errnum = Z_OK;
+    unsafe {
+        gzerror(file, &mut errnum);
+    }
+    ZlibError::from(errnum)
+}
+
+// Whether a read that got nothing stopped at the end of `file`. A file cut off
+// partway through a member ends too, but leaves Z_BUF_ERROR "unexpected end of
+// file" in gzerror, that's `DataError`, the input is incomplete.
+fn gz_end(file: GzFile) -> Result<(), ZlibError> {
+    let mut errnum = Z_OK;
+    unsafe {
+        gzerror(file, &mut errnum);
+    }
+    match errnum {
+        Z_OK if unsafe { gzeof(file) } != 0 => Ok(()),
+        Z_OK => Err(ZlibError::StreamError),
+        Z_BUF_ERROR => Err(ZlibError::DataError),
+        _ => Err(ZlibError::from(errnum)),
+    }
+}
+
+// The zlib error code and message of the last failed operation on `file`.
+fn gz_last_error(file: GzFile) -> Option<(i32, String)> {
+    let mut errnum = Z_OK;
+    let message = unsafe { gzerror(file, &mut errnum) };
+    if errnum == Z_OK {
+        return None;
+    }
+    // For Z_ERRNO the message is just "", ask the OS instead.
+    let message = if errnum == Z_ERRNO || message.is_null() {
+        std::io::Error::last_os_error().to_string()
+    } else {
+        unsafe { CStr::from_ptr(message) }
+            .to_string_lossy()
+            .into_owned()
+    };
+    Some((errnum, message))
+}
+
+fn gz_buffer(file: GzFile, size: u32) -> Result<(), ZlibError> {
+    let code = unsafe { gzbuffer(file, size as c_uint) };
+    if code != Z_OK {
+        return Err(ZlibError::StreamError);
+    }
+    Ok(())
+}
+
+fn gz_offset(file: GzFile) -> Result<u64, ZlibError> {
+    let offset = gz_offset_off(file);
+    if offset < 0 {
+        return Err(ZlibError::StreamError);
+    }
+    Ok(offset as u64)
+}
+
+fn gz_tell(file: GzFile) -> Result<u64, ZlibError> {
+    let position = gz_tell_off(file);
+    if position < 0 {
+        return Err(ZlibError::StreamError);
+    }
+    Ok(position as u64)
+}
+
+// gzread takes the length as an unsigned int but returns the count as an int,
+// so it can't read more than that at once.
+const GZREAD_MAX: usize = c_int::MAX as usize;
+const _: () = assert!(GZREAD_MAX <= u32::MAX as usize);
+
+impl std::io::Read for GzReader {
+    // Buffers larger than gzread can fill at once take several calls.
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let mut total = 0;
+        for chunk in buf.chunks_mut(GZREAD_MAX) {
+            let len = chunk.len() as c_uint;
+            let bytes_read = unsafe { gzread(self.file.as_ptr(), chunk.as_mut_ptr(), len) };
+            if bytes_read < 0 {
+                // What was read before the error is still good, the error
+                // sticks and the next call reports it.
+                if total > 0 {
+                    break;
+                }
+                return Err(gz_error(self.file.as_ptr()).into());
+            }
+            // Nothing at all, the end of the file or of what's left of it.
+            if bytes_read == 0 && total == 0 {
+                // Cut off, like `GzDecoder` running out of input.
+                if gz_last_error(self.file.as_ptr())
+                    .is_some_and(|(errnum, _)| errnum == Z_BUF_ERROR)
+                {
+                    return Err(std::io::ErrorKind::UnexpectedEof.into());
+                }
+                gz_end(self.file.as_ptr())?;
+                return Ok(0);
+            }
+            total += bytes_read as usize;
+            // A short read means the end of the file.
+            if (bytes_read as c_uint) < len {
+                break;
+            }
+        }
+        Ok(total)
+    }
+}
+
+// Positions are offsets in the uncompressed data.
+// zlib emulates seeking in a compressed file: a forward seek decompresses and
+// discards everything up to the new position, a backward seek starts over from
+// the beginning of the file. Either way it's only cheap for short forward jumps.
+// Seeking relative to the end isn't supported, the uncompressed size is unknown.
+impl std::io::Seek for GzReader {
+    fn seek(&mut self, pos: std::io::SeekFrom) -> std::io::Result<u64> {
+        let (offset, whence) = match pos {
+            std::io::SeekFrom::Start(offset) => (ZOff::try_from(offset).ok(), SEEK_SET),
+            std::io::SeekFrom::Current(offset) => (ZOff::try_from(offset).ok(), SEEK_CUR),
+            std::io::SeekFrom::End(_) => (None, 0),
+        };
+        let Some(offset) = offset else {
+            return Err(std::io::ErrorKind::InvalidInput.into());
+        };
+        let position = gz_seek_off(self.file.as_ptr(), offset, whence);
+        if position < 0 {
+            return Err(gz_error(self.file.as_ptr()).into());
+        }
+        Ok(position as u64)
+    }
+}
+
+// Decompresses the gz file at `input` into `output`, piece by piece, so neither
+// has to fit in memory, and the contents can be anything, not only text.
+// Returns how many bytes were written. Fails with `StreamError` if `input`
+// isn't valid UTF-8, as `GzReader::open` takes a `&str`.
+pub fn decompress_file(input: &Path, output: &Path) -> Result<u64, ZlibError> {
+    let name = input.to_str().ok_or(ZlibError::StreamError)?;
+    let mut reader = GzReader::open(name)?;
+    let mut dest = std::fs::File::create(output)?;
+    Ok(std::io::copy(&mut reader, &mut dest)?)
+}
+
+// Iterates over the lines of a gz file, without the trailing "\n" or "\r\n".
+pub struct GzLines {
+    reader: GzReader,
+    buffer: [c_char; 0x1000],
+}
+
+// Opens a gz file to read it line by line, as opposed to loading it all with `read_gz_file`.
+pub fn gz_lines(path: &str) -> Result<GzLines, ZlibError> {
+    let reader = GzReader::open(path)?;
+    Ok(GzLines {
+        reader,
+        buffer: [0; 0x1000],
+    })
+}
+
+impl Iterator for GzLines {
+    type Item = Result<String, ZlibError>;
+
+    fn next(&mut self) -> Option<Result<String, ZlibError>> {
+        let file = self.reader.file.as_ptr();
+        let mut line = Vec::new();
+        // gzgets stops at the end of the buffer, keep going until the newline.
+        while !line.ends_with(b"\n") {
+            let before = gz_tell_off(file);
+            let chunk =
+                unsafe { gzgets(file, self.buffer.as_mut_ptr(), self.buffer.len() as c_int) };
+            if chunk.is_null() {
+                if let Err(err) = gz_end(file) {
+                    return Some(Err(err));
+                }
+                // The last line doesn't have to end with a newline.
+                if line.is_empty() {
+                    return None;
+                }
+                break;
+            }
+            // gzgets NUL-terminates what it read, but the line itself can hold
+            // a NUL, the position moved by as many bytes as were read.
+            let read = (gz_tell_off(file) - before) as usize;
+            let chunk = unsafe { std::slice::from_raw_parts(chunk as *const u8, read) };
+            line.extend_from_slice(chunk);
+        }
+
+        if line.ends_with(b"\n") {
+            line.pop();
+            if line.ends_with(b"\r") {
+                line.pop();
+            }
+        }
+        Some(String::from_utf8(line).map_err(|_| ZlibError::DataError))
+    }
+}
+
+// Writes a gzip file as a regular `std::io::Write`.
+pub struct GzWriter {
+    file: GzHandle,
+}
+
+impl GzWriter {
+    // Creates the file, or truncates it if it already exists.
+    pub fn create(name: &str) -> Result<GzWriter, ZlibError> {
+        GzWriter::open(name, GzMode::Write(CompressionLevel::Default))
+    }
+
+    // Fails with `StreamError` when asked to open the file for reading.
+    pub fn open(name: &str, mode: GzMode) -> Result<GzWriter, ZlibError> {
+        if mode == GzMode::Read {
+            return Err(ZlibError::StreamError);
+        }
+        let file = gz_open(name, mode)?;
+        Ok(GzWriter { file })
+    }
+
+    // Writes to an already open file descriptor, e.g. stdout (1) or a socket.
+    // The descriptor is owned by the writer from now on and is closed when it's dropped.
+    // Fails with `StreamError` when asked to open the descriptor for reading.
+    #[cfg(unix)]
+    pub fn from_raw_fd(fd: RawFd, mode: GzMode) -> Result<GzWriter, ZlibError> {
+        if mode == GzMode::Read {
+            return Err(ZlibError::StreamError);
+        }
+        let file = gz_dopen(fd, mode)?;
+        Ok(GzWriter { file })
+    }
+
+    // The zlib error code and message of the last failed operation, if any,
+    // e.g. (-3, "file.gz: incorrect data check") for a corrupted file.
+    pub fn last_error(&self) -> Option<(i32, String)> {
+        gz_last_error(self.file.as_ptr())
+    }
+
+    // Forgets the last error, so the handle can be used again after a recoverable one.
+    pub fn clear_error(&mut self) {
+        unsafe { gzclearerr(self.file.as_ptr()) }
+    }
+
+    // Sets the size of zlib's internal buffers, 8 KB by default.
+    // Must be called before the first write, otherwise fails with `StreamError`.
+    pub fn set_buffer_size(&mut self, size: u32) -> Result<(), ZlibError> {
+        gz_buffer(self.file.as_ptr(), size)
+    }
+
+    // Changes the compression level and strategy for the data written from now on,
+    // e.g. to stop trying to compress a section of already compressed data.
+    pub fn set_params(
+        &mut self,
+        level: CompressionLevel,
+        strategy: Strategy,
+    ) -> Result<(), ZlibError> {
+        let level = c_int::try_from(level)?;
+        let code = unsafe { gzsetparams(self.file.as_ptr(), level, c_int::from(strategy)) };
+        if code != Z_OK {
+            return Err(ZlibError::from(code));
+        }
+        Ok(())
+    }
+
+    // Writes a string, e.g. a line for `gz_lines` to read back.
+    // Returns the number of bytes written.
+    // Fails with `StreamError` if `s` contains a NUL byte, C would cut the string there.
+    pub fn put_str(&mut self, s: &str) -> Result<usize, ZlibError> {
+        let c_s = CString::new(s).map_err(|_| ZlibError::StreamError)?;
+        let written = unsafe { gzputs(self.file.as_ptr(), c_s.as_ptr()) };
+        if written < 0 {
+            return Err(gz_error(self.file.as_ptr()));
+        }
+        Ok(written as usize)
+    }
+
+    // Writes a single byte, cheaper than a one-byte `write`.
+    pub fn write_byte(&mut self, b: u8) -> Result<(), ZlibError> {
+        let written = unsafe { gzputc(self.file.as_ptr(), c_int::from(b)) };
+        if written < 0 {
+            return Err(gz_error(self.file.as_ptr()));
+        }
+        Ok(())
+    }
+
+    // Writes all of `items` as raw bytes, like C's fwrite, for `read_items`
+    // to read back. Returns how many items were written.
+    pub fn write_items<T: GzItem>(&mut self, items: &[T]) -> Result<usize, ZlibError> {
+        if items.is_empty() || size_of::<T>() == 0 {
+            return Ok(0);
+        }
+        let file = self.file.as_ptr();
+        let items_ptr = items.as_ptr().cast::<c_void>();
+        let written = unsafe { gzfwrite(items_ptr, size_of::<T>(), items.len(), file) };
+        if written == 0 {
+            return Err(gz_error(file));
+        }
+        Ok(written)
+    }
+
+    // Formats `args` and writes the result, a Rust take on the variadic gzprintf.
+    // Via `std::io::Write::write_fmt`, `write!(writer, "{}\n", record)?` works too.
+    pub fn write_fmt_gz(&mut self, args: std::fmt::Arguments) -> Result<(), ZlibError> {
+        let formatted = std::fmt::format(args);
+        let mut buf = formatted.as_bytes();
+        while !buf.is_empty() {
+            let written = self.write_bytes(buf)?;
+            buf = &buf[written..];
+        }
+        Ok(())
+    }
+
+    // Returns how many bytes of `buf` were taken, which is less than all of
+    // them for buffers of 2 GB or more, or when writing to the file failed
+    // halfway, `write_all` and `write_fmt_gz` carry on with the rest.
+    fn write_bytes(&mut self, buf: &[u8]) -> Result<usize, ZlibError> {
+        // gzwrite returns the count as an int, so it can't write more than that at once.
+        let len = buf.len().min(c_int::MAX as usize) as c_uint;
+        if len == 0 {
+            return Ok(0);
+        }
+        let file = self.file.as_ptr();
+        let before = gz_tell_off(file);
+        let written = unsafe { gzwrite(file, buf.as_ptr(), len) };
+        if written > 0 {
+            return Ok(written as usize);
+        }
+        // gzwrite returns 0 on an error even when it took part of `buf` before,
+        // the position still counts that part. The next write fails right away.
+        let taken = gz_tell_off(file) - before;
+        if before >= 0 && taken > 0 {
+            return Ok(taken as usize);
+        }
+        Err(gz_error(file))
+    }
+
+    // Flushes the buffered data to the file.
+    // `Finish` ends the current gzip member, anything written after it starts a new one.
+    pub fn flush_mode(&mut self, mode: FlushMode) -> Result<(), ZlibError> {
+        let code = unsafe { gzflush(self.file.as_ptr(), c_int::from(mode)) };
+        if code != Z_OK {
+            return Err(ZlibError::from(code));
+        }
+        Ok(())
+    }
+
+    // How many uncompressed bytes were written so far.
+    // Like `GzReader::tell`, this is not the size of the file on disk.
+    pub fn tell(&self) -> Result<u64, ZlibError> {
+        gz_tell(self.file.as_ptr())
+    }
+
+    // How many compressed bytes went to the file on disk so far, as opposed
+    // to `tell`'s count of uncompressed bytes written. What zlib still buffers
+    // isn't counted, it's only up to date after a flush.
+    pub fn compressed_offset(&self) -> Result<u64, ZlibError> {
+        gz_offset(self.file.as_ptr())
+    }
+
+    // Finishes the gzip stream, flushes it to the file and closes it.
+    // Dropping the writer does the same but has to ignore errors, so whoever
+    // needs to know the data made it to disk must call `close` instead.
+    // Fails with `Errno` if the last write failed, e.g. `ENOSPC` when the disk is full.
+    pub fn close(self) -> Result<(), ZlibError> {
+        self.file.close()
+    }
+}
+
+impl std::io::Write for GzWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        Ok(self.write_bytes(buf)?)
+    }
+
+    // Pushes everything written so far to the file without ending the gzip stream.
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(self.flush_mode(FlushMode::SyncFlush)?)
+    }
+}
@@ -0,0 +1,218 @@
+#[cfg(not(feature = "std"))]
+use alloc::{boxed::Box, string::String, vec, vec::Vec};
+use core::ptr;
+
+use libc::{c_int, c_uint, c_ulong};
+
+use crate::error::ZlibError;
+
+// typedef struct gz_header_s {
+//     int     text;       /* true if compressed data believed to be text */
+//     uLong   time;       /* modification time */
+//     int     xflags;     /* extra flags (not used when writing a gzip file) */
+//     int     os;         /* operating system */
+//     Bytef   *extra;     /* pointer to extra field or Z_NULL if none */
+//     uInt    extra_len;  /* extra field length (valid if extra != Z_NULL) */
+//     uInt    extra_max;  /* space at extra (only when reading header) */
+//     Bytef   *name;      /* pointer to zero-terminated file name or Z_NULL */
+//     uInt    name_max;   /* space at name (only when reading header) */
+//     Bytef   *comment;   /* pointer to zero-terminated comment or Z_NULL */
+//     uInt    comm_max;   /* space at comment (only when reading header) */
+//     int     hcrc;       /* true if there was or will be a header crc */
+//     int     done;       /* true when done reading gzip header (not used
+//                            when writing a gzip file) */
+// } gz_header;
+#[repr(C)]
+#[derive(Clone)]
+pub(crate) struct GzHeader {
+    text: c_int,
+    time: c_ulong,
+    xflags: c_int,
+    os: c_int,
+    extra: *mut u8,
+    extra_len: c_uint,
+    extra_max: c_uint,
+    name: *mut u8,
+    name_max: c_uint,
+    comment: *mut u8,
+    comm_max: c_uint,
+    hcrc: c_int,
+    pub(crate) done: c_int,
+}
+
+impl GzHeader {
+    pub(crate) fn new() -> GzHeader {
+        GzHeader {
+            text: 0,
+            time: 0,
+            xflags: 0,
+            os: 0,
+            extra: ptr::null_mut(),
+            extra_len: 0,
+            extra_max: 0,
+            name: ptr::null_mut(),
+            name_max: 0,
+            comment: ptr::null_mut(),
+            comm_max: 0,
+            hcrc: 0,
+            done: 0,
+        }
+    }
+}
+
+// Metadata from the header of a gzip member.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GzipHeader {
+    // The original file name, as `gzip(1)` stores it unless given `-n`.
+    pub name: Option<String>,
+    pub comment: Option<String>,
+    // Modification time of the original file in seconds since the epoch, 0 if unknown.
+    pub mtime: u32,
+    // The operating system the member was made on, 3 for Unix, 255 if unknown.
+    pub os: u8,
+}
+
+// The `os` gzip(1) writes on this platform.
+const GZIP_OS_NATIVE: u8 = if cfg!(unix) { 3 } else { 255 };
+
+// Builds the header for `Deflater::set_header` and `gzip_compress_with_header`.
+pub struct GzipHeaderBuilder {
+    header: GzipHeader,
+    reproducible: bool,
+}
+
+impl GzipHeaderBuilder {
+    pub fn new() -> GzipHeaderBuilder {
+        GzipHeaderBuilder {
+            header: GzipHeader {
+                os: GZIP_OS_NATIVE,
+                ..GzipHeader::default()
+            },
+            reproducible: false,
+        }
+    }
+
+    pub fn name(mut self, name: impl Into<String>) -> GzipHeaderBuilder {
+        self.header.name = Some(name.into());
+        self
+    }
+
+    pub fn comment(mut self, comment: impl Into<String>) -> GzipHeaderBuilder {
+        self.header.comment = Some(comment.into());
+        self
+    }
+
+    pub fn mtime(mut self, mtime: u32) -> GzipHeaderBuilder {
+        self.header.mtime = mtime;
+        self
+    }
+
+    pub fn os(mut self, os: u8) -> GzipHeaderBuilder {
+        self.header.os = os;
+        self
+    }
+
+    // Zeroes mtime whatever `mtime` was given, so compressing the same input
+    // gives the same bytes every time, as `gzip -n` does for the timestamp.
+    pub fn reproducible(mut self, reproducible: bool) -> GzipHeaderBuilder {
+        self.reproducible = reproducible;
+        self
+    }
+
+    pub fn build(self) -> GzipHeader {
+        let mut header = self.header;
+        if self.reproducible {
+            header.mtime = 0;
+        }
+        header
+    }
+}
+
+impl Default for GzipHeaderBuilder {
+    fn default() -> GzipHeaderBuilder {
+        GzipHeaderBuilder::new()
+    }
+}
+
+// Longer file names and comments are cut to this many bytes.
+const GZIP_HEADER_FIELD_MAX: usize = 1024;
+
+// What zlib fills in while inflating or reads while deflating, through the
+// pointers in `raw`, so none of it may move. Kept boxed inside the stream.
+pub(crate) struct HeaderSlot {
+    pub(crate) raw: GzHeader,
+    name: Vec<u8>,
+    comment: Vec<u8>,
+}
+
+impl HeaderSlot {
+    pub(crate) fn new() -> HeaderSlot {
+        let mut slot = HeaderSlot {
+            raw: GzHeader::new(),
+            name: vec![0; GZIP_HEADER_FIELD_MAX],
+            comment: vec![0; GZIP_HEADER_FIELD_MAX],
+        };
+        slot.raw.name = slot.name.as_mut_ptr();
+        slot.raw.name_max = GZIP_HEADER_FIELD_MAX as c_uint;
+        slot.raw.comment = slot.comment.as_mut_ptr();
+        slot.raw.comm_max = GZIP_HEADER_FIELD_MAX as c_uint;
+        slot
+    }
+
+    pub(crate) fn from_header(header: &GzipHeader) -> Result<Box<HeaderSlot>, ZlibError> {
+        // Zero-terminated, with no NUL inside.
+        let field = |value: &Option<String>| match value {
+            Some(value) if value.contains('\0') => Err(ZlibError::StreamError),
+            Some(value) => Ok(Some([value.as_bytes(), &[0]].concat())),
+            None => Ok(None),
+        };
+        let mut slot = Box::new(HeaderSlot {
+            raw: GzHeader::new(),
+            name: field(&header.name)?.unwrap_or_default(),
+            comment: field(&header.comment)?.unwrap_or_default(),
+        });
+        if header.name.is_some() {
+            slot.raw.name = slot.name.as_mut_ptr();
+        }
+        if header.comment.is_some() {
+            slot.raw.comment = slot.comment.as_mut_ptr();
+        }
+        slot.raw.time = header.mtime as c_ulong;
+        slot.raw.os = header.os as c_int;
+        Ok(slot)
+    }
+
+    // A copy with the pointers into its own buffers, for a copy of the stream.
+    pub(crate) fn duplicate(&self) -> Box<HeaderSlot> {
+        let mut slot = Box::new(HeaderSlot {
+            raw: self.raw.clone(),
+            name: self.name.clone(),
+            comment: self.comment.clone(),
+        });
+        if !slot.raw.name.is_null() {
+            slot.raw.name = slot.name.as_mut_ptr();
+        }
+        if !slot.raw.comment.is_null() {
+            slot.raw.comment = slot.comment.as_mut_ptr();
+        }
+        slot
+    }
+
+    pub(crate) fn to_header(&self) -> GzipHeader {
+        // zlib leaves the pointer null if the field is absent, and doesn't
+        // terminate a field it had to cut.
+        let field = |ptr: *mut u8, buffer: &[u8]| {
+            if ptr.is_null() {
+                return None;
+            }
+            let len = buffer.iter().position(|&b| b == 0).unwrap_or(buffer.len());
+            Some(String::from_utf8_lossy(&buffer[..len]).into_owned())
+        };
+        GzipHeader {
+            name: field(self.raw.name, &self.name),
+            comment: field(self.raw.comment, &self.comment),
+            mtime: self.raw.time as u32,
+            os: self.raw.os as u8,
+        }
+    }
+}
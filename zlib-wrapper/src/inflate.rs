@@ -0,0 +1,466 @@
+use alloc::sync::Arc;
+#[cfg(not(feature = "std"))]
+use alloc::{boxed::Box, vec, vec::Vec};
+use core::ptr;
+
+use libc::{c_char, c_int, c_uint};
+
+use crate::checksum::crc32_combine;
+use crate::deflate::{MAX_WBITS, Z_NO_FLUSH};
+use crate::error::{Z_BUF_ERROR, Z_OK, Z_STREAM_END, ZlibError};
+use crate::header::{GzHeader, GzipHeader, HeaderSlot};
+use crate::stream::{Allocator, Format, SharedAllocator, ZStream};
+use crate::version::ZLIB_VERSION;
+
+#[cfg_attr(not(any(feature = "bundled", feature = "zlib-ng")), link(name = "z"))]
+unsafe extern "C" {
+    // ZEXTERN int ZEXPORT inflateInit2_ OF((z_streamp strm, int  windowBits,
+    //                                       const char *version, int stream_size));
+    // ZEXTERN int ZEXPORT inflate OF((z_streamp strm, int flush));
+    // ZEXTERN int ZEXPORT inflateEnd OF((z_streamp strm));
+    // ZEXTERN int ZEXPORT inflateReset OF((z_streamp strm));
+    // ZEXTERN int ZEXPORT inflateReset2 OF((z_streamp strm, int windowBits));
+    // ZEXTERN int ZEXPORT inflateSetDictionary OF((z_streamp strm,
+    //                                              const Bytef *dictionary,
+    //                                              uInt  dictLength));
+    // ZEXTERN int ZEXPORT inflateGetDictionary OF((z_streamp strm,
+    //                                              Bytef *dictionary,
+    //                                              uInt  *dictLength));
+    // ZEXTERN int ZEXPORT inflateGetHeader OF((z_streamp strm,
+    //                                          gz_headerp head));
+    // ZEXTERN int ZEXPORT inflateCopy OF((z_streamp dest,
+    //                                     z_streamp source));
+    // ZEXTERN int ZEXPORT inflateValidate OF((z_streamp, int));
+    //
+    // inflateInit2 is a macro passing ZLIB_VERSION and sizeof(z_stream) to inflateInit2_.
+
+    unsafe fn inflateInit2_(
+        strm: *mut ZStream,
+        window_bits: c_int,
+        version: *const c_char,
+        stream_size: c_int,
+    ) -> c_int;
+    unsafe fn inflate(strm: *mut ZStream, flush: c_int) -> c_int;
+    unsafe fn inflateEnd(strm: *mut ZStream) -> c_int;
+    unsafe fn inflateReset(strm: *mut ZStream) -> c_int;
+    unsafe fn inflateReset2(strm: *mut ZStream, window_bits: c_int) -> c_int;
+    unsafe fn inflateSetDictionary(
+        strm: *mut ZStream,
+        dictionary: *const u8,
+        dict_length: c_uint,
+    ) -> c_int;
+    unsafe fn inflateGetDictionary(
+        strm: *mut ZStream,
+        dictionary: *mut u8,
+        dict_length: *mut c_uint,
+    ) -> c_int;
+    unsafe fn inflateGetHeader(strm: *mut ZStream, head: *mut GzHeader) -> c_int;
+    unsafe fn inflateCopy(dest: *mut ZStream, source: *mut ZStream) -> c_int;
+    unsafe fn inflateValidate(strm: *mut ZStream, check: c_int) -> c_int;
+}
+
+// A limit for `Inflater::set_max_ratio`. Text and most binary data don't
+// expand past 10:1, even a file filled with a single byte only expands about 1030:1.
+pub const DEFAULT_MAX_RATIO: u64 = 1000;
+
+// The ratio isn't checked before the output is this large, so a small message
+// compressing really well doesn't trip it.
+const MAX_RATIO_GRACE: u64 = 0x10000;
+
+// Decompresses data piece by piece, e.g. as it arrives over a socket.
+pub struct Inflater {
+    // Boxed for the same reason as in `Deflater`.
+    stream: Box<ZStream>,
+    // Counted on the Rust side, zlib doesn't update its totals when it returns Z_NEED_DICT.
+    total_in: u64,
+    total_out: u64,
+    header: Option<Box<HeaderSlot>>,
+    max_output: Option<u64>,
+    max_ratio: Option<u64>,
+    validate: bool,
+    // Same as in `Deflater`.
+    allocator: Option<SharedAllocator>,
+}
+
+// Safe for the same reasons as `Deflater`.
+unsafe impl Send for Inflater {}
+
+impl Inflater {
+    pub fn new(format: Format) -> Result<Inflater, ZlibError> {
+        Inflater::init(format, None)
+    }
+
+    // Same as `new`, with the internal state allocated by `allocator`.
+    pub fn with_allocator(
+        format: Format,
+        allocator: Box<dyn Allocator>,
+    ) -> Result<Inflater, ZlibError> {
+        Inflater::init(format, Some(Arc::new(allocator)))
+    }
+
+    fn init(format: Format, allocator: Option<SharedAllocator>) -> Result<Inflater, ZlibError> {
+        let mut stream = Box::new(ZStream::with_allocator(allocator.as_ref()));
+        let code = unsafe {
+            inflateInit2_(
+                &mut *stream,
+                format.window_bits(),
+                ZLIB_VERSION.as_ptr(),
+                size_of::<ZStream>() as c_int,
+            )
+        };
+        if code != Z_OK {
+            return Err(ZlibError::from(code));
+        }
+        Ok(Inflater {
+            stream,
+            total_in: 0,
+            total_out: 0,
+            header: None,
+            max_output: None,
+            max_ratio: None,
+            validate: true,
+            allocator,
+        })
+    }
+
+    // Asks zlib to keep the gzip header of the stream, read it back with `header`.
+    // Must be called before the header is decompressed, it's kept across resets.
+    // Fails with `StreamError` for any format but `Gzip` and `GzipOrZlib`.
+    pub fn track_header(&mut self) -> Result<(), ZlibError> {
+        let slot = self.header.insert(Box::new(HeaderSlot::new()));
+        let code = unsafe { inflateGetHeader(&mut *self.stream, &mut slot.raw) };
+        if code != Z_OK {
+            self.header = None;
+            return Err(ZlibError::from(code));
+        }
+        Ok(())
+    }
+
+    // The gzip header of the current stream, once it has been decompressed
+    // entirely. `None` until then, without `track_header`, or for zlib streams.
+    pub fn header(&self) -> Option<GzipHeader> {
+        let slot = self.header.as_ref()?;
+        (slot.raw.done == 1).then(|| slot.to_header())
+    }
+
+    // Makes `decompress` fail with `LimitExceeded` once a stream decompresses
+    // to more than `max_output` bytes in total, it stays set across resets.
+    // Set one for untrusted input, a few KB of it can decompress to GBs.
+    pub fn set_max_output(&mut self, max_output: Option<u64>) {
+        self.max_output = max_output;
+    }
+
+    // Makes `decompress` fail with `LimitExceeded` once a stream decompressed to
+    // more than `max_ratio` times the compressed bytes consumed so far. Catches
+    // decompression bombs long before a size limit would, as they expand far
+    // more than real data. `DEFAULT_MAX_RATIO` is a sane choice, `None` disables
+    // it, which is the default. Stays set across resets.
+    pub fn set_max_ratio(&mut self, max_ratio: Option<u64>) {
+        self.max_ratio = max_ratio;
+    }
+
+    // Whether the checksum in the zlib or gzip trailer is computed and checked,
+    // it is by default. Turning it off saves the time spent on it for trusted
+    // data, a corrupted stream then decompresses to garbage instead of failing
+    // with `DataError`. Raw streams have no checksum, so it does nothing for
+    // those. Stays set across resets.
+    pub fn set_validate(&mut self, validate: bool) -> Result<(), ZlibError> {
+        let code = unsafe { inflateValidate(&mut *self.stream, c_int::from(validate)) };
+        if code != Z_OK {
+            return Err(ZlibError::from(code));
+        }
+        self.validate = validate;
+        Ok(())
+    }
+
+    // Decompresses as much of `input` into `output` as fits.
+    // Returns how many bytes were consumed from `input`, produced into `output`,
+    // and whether the end of the compressed stream was reached.
+    //
+    // Fails with `NeedDict` if the data was compressed with a preset dictionary.
+    // Pass it to `set_dictionary` and carry on from `total_in`, the stream header
+    // has been consumed already.
+    pub fn decompress(
+        &mut self,
+        input: &[u8],
+        output: &mut [u8],
+    ) -> Result<(usize, usize, bool), ZlibError> {
+        // Room for one byte past the limit, to tell reaching it from going over.
+        let output = match self.max_output {
+            Some(max_output) => {
+                let room = max_output.saturating_sub(self.total_out).saturating_add(1);
+                let len = output
+                    .len()
+                    .min(usize::try_from(room).unwrap_or(usize::MAX));
+                &mut output[..len]
+            }
+            None => output,
+        };
+        let (avail_in, avail_out) = self.stream.set_buffers(input, output);
+        let code = unsafe { inflate(&mut *self.stream, Z_NO_FLUSH) };
+        let consumed = (avail_in - self.stream.avail_in) as usize;
+        let produced = (avail_out - self.stream.avail_out) as usize;
+        self.stream.clear_buffers();
+        self.total_in += consumed as u64;
+        self.total_out += produced as u64;
+        if self
+            .max_output
+            .is_some_and(|max_output| self.total_out > max_output)
+        {
+            return Err(ZlibError::LimitExceeded);
+        }
+        if let Some(max_ratio) = self.max_ratio
+            && self.total_out > MAX_RATIO_GRACE
+            && self.total_out / max_ratio.max(1) > self.total_in
+        {
+            return Err(ZlibError::LimitExceeded);
+        }
+
+        match code {
+            // Z_BUF_ERROR only means no progress was possible with these buffers.
+            Z_OK | Z_BUF_ERROR => Ok((consumed, produced, false)),
+            Z_STREAM_END => Ok((consumed, produced, true)),
+            _ => Err(ZlibError::from(code)),
+        }
+    }
+
+    // A copy of the stream in its current state, which carries on independently.
+    // E.g. keep copies at points along a large stream, then seek by copying the
+    // nearest one instead of decompressing from the start.
+    pub fn try_clone(&self) -> Result<Inflater, ZlibError> {
+        let mut stream = Box::new(ZStream::new());
+        // inflateCopy only reads the source.
+        let source = ptr::from_ref(&*self.stream).cast_mut();
+        let code = unsafe { inflateCopy(&mut *stream, source) };
+        if code != Z_OK {
+            return Err(ZlibError::from(code));
+        }
+        let mut clone = Inflater {
+            stream,
+            total_in: self.total_in,
+            total_out: self.total_out,
+            header: None,
+            max_output: self.max_output,
+            max_ratio: self.max_ratio,
+            validate: self.validate,
+            allocator: self.allocator.clone(),
+        };
+        // Same as in `Deflater::try_clone`. inflateGetHeader clears `done`,
+        // the copy of the header may well have been read already.
+        if let Some(header) = &self.header {
+            let slot = clone.header.insert(header.duplicate());
+            let done = slot.raw.done;
+            let code = unsafe { inflateGetHeader(&mut *clone.stream, &mut slot.raw) };
+            if code != Z_OK {
+                return Err(ZlibError::from(code));
+            }
+            slot.raw.done = done;
+        }
+        Ok(clone)
+    }
+
+    // Starts decompressing a new stream of the same format, keeping the allocated state.
+    // Much cheaper than creating a new `Inflater` for every frame.
+    pub fn reset(&mut self) -> Result<(), ZlibError> {
+        let code = unsafe { inflateReset(&mut *self.stream) };
+        self.after_reset(code)
+    }
+
+    // Same as `reset`, but the new stream is in a different format.
+    pub fn reset_format(&mut self, format: Format) -> Result<(), ZlibError> {
+        let code = unsafe { inflateReset2(&mut *self.stream, format.window_bits()) };
+        self.after_reset(code)
+    }
+
+    fn after_reset(&mut self, code: c_int) -> Result<(), ZlibError> {
+        if code != Z_OK {
+            return Err(ZlibError::from(code));
+        }
+        self.total_in = 0;
+        self.total_out = 0;
+        // inflateReset forgets the header, hand zlib a fresh one.
+        if self.header.is_some() {
+            self.track_header()?;
+        }
+        // inflateReset2 turns validation back on.
+        if !self.validate {
+            self.set_validate(false)?;
+        }
+        Ok(())
+    }
+
+    // Supplies the dictionary the data was compressed with, after `decompress`
+    // failed with `NeedDict`. For `Format::Raw` it can be set up front instead.
+    // Fails with `DataError` if it's not the dictionary the stream expects.
+    pub fn set_dictionary(&mut self, dict: &[u8]) -> Result<(), ZlibError> {
+        let dict_length = c_uint::try_from(dict.len()).map_err(|_| ZlibError::StreamError)?;
+        let code = unsafe { inflateSetDictionary(&mut *self.stream, dict.as_ptr(), dict_length) };
+        if code != Z_OK {
+            return Err(ZlibError::from(code));
+        }
+        Ok(())
+    }
+
+    // The sliding window, the last 32 KB decompressed at most, oldest byte first.
+    // A fresh raw `Inflater` primed with it through `set_dictionary` carries on
+    // from a block boundary here, without decompressing what came before.
+    pub fn get_dictionary(&self) -> Result<Vec<u8>, ZlibError> {
+        let mut dict = vec![0u8; 1 << MAX_WBITS];
+        let mut dict_length: c_uint = 0;
+        // inflateGetDictionary only reads the stream, and the window is never
+        // larger than 1 << MAX_WBITS.
+        let strm = ptr::from_ref(&*self.stream).cast_mut();
+        let code = unsafe { inflateGetDictionary(strm, dict.as_mut_ptr(), &mut dict_length) };
+        if code != Z_OK {
+            return Err(ZlibError::from(code));
+        }
+        dict.truncate(dict_length as usize);
+        Ok(dict)
+    }
+
+    // The checksum of the data decompressed so far: CRC-32 for gzip, Adler-32
+    // for zlib, what `crc32` or `adler32` return for it. Meaningless for raw
+    // streams, before a `GzipOrZlib` stream's header told which it is, or
+    // with validation off. After `NeedDict` it's the Adler-32 of the
+    // dictionary the stream expects instead.
+    pub fn checksum(&self) -> u32 {
+        self.stream.adler as u32
+    }
+
+    // How many compressed bytes were consumed since the stream started.
+    pub fn total_in(&self) -> u64 {
+        self.total_in
+    }
+
+    // How many decompressed bytes were produced since the stream started.
+    pub fn total_out(&self) -> u64 {
+        self.total_out
+    }
+}
+
+impl Drop for Inflater {
+    fn drop(&mut self) {
+        unsafe {
+            inflateEnd(&mut *self.stream);
+        }
+    }
+}
+
+// Decompresses gzip data in memory. Handles several gzip members one after
+// another, as produced by `cat a.gz b.gz`, concatenating their contents.
+pub fn gzip_decompress(source: &[u8]) -> Result<Vec<u8>, ZlibError> {
+    gzip_decompress_with_header(source).map(|(dest, _)| dest)
+}
+
+// Same as `gzip_decompress`, also returning the header of the first member,
+// e.g. to restore the original file name.
+pub fn gzip_decompress_with_header(source: &[u8]) -> Result<(Vec<u8>, GzipHeader), ZlibError> {
+    let mut inflater = Inflater::new(Format::Gzip)?;
+    inflater.track_header()?;
+    let mut header = None;
+    let mut dest = Vec::new();
+    let mut buffer = [0u8; 0x8000];
+    let mut input = source;
+    loop {
+        let (consumed, produced, done) = inflater.decompress(input, &mut buffer)?;
+        dest.extend_from_slice(&buffer[..produced]);
+        input = &input[consumed..];
+        if header.is_none() {
+            header = inflater.header();
+        }
+        if done {
+            if input.is_empty() {
+                // A finished member always has its header read.
+                return Ok((dest, header.unwrap_or_default()));
+            }
+            // Another member follows.
+            inflater.reset()?;
+        } else if consumed == 0 && produced == 0 {
+            // The input ended in the middle of a member.
+            return Err(ZlibError::DataError);
+        }
+    }
+}
+
+// Checks the gzip data in `source` without keeping what it decompresses to:
+// every member is inflated into a scratch buffer so zlib can check its CRC-32
+// and ISIZE footer. Returns the CRC-32 of the whole decompressed content.
+pub fn gzip_verify(source: &[u8]) -> Result<u32, ZlibError> {
+    let mut inflater = Inflater::new(Format::Gzip)?;
+    let mut buffer = [0u8; 0x8000];
+    let mut input = source;
+    let mut crc = 0;
+    loop {
+        let (consumed, produced, done) = inflater.decompress(input, &mut buffer)?;
+        input = &input[consumed..];
+        if done {
+            // Once a gzip member ends, `adler` holds its checked CRC-32.
+            let member_crc = inflater.stream.adler as u32;
+            crc = crc32_combine(crc, member_crc, inflater.total_out());
+            if input.is_empty() {
+                return Ok(crc);
+            }
+            inflater.reset()?;
+        } else if consumed == 0 && produced == 0 {
+            return Err(ZlibError::DataError);
+        }
+    }
+}
+
+// Decompresses `source` whatever its format: gzip, zlib or raw deflate.
+// The first bytes decide which to try first: 0x1f 0x8b starts a gzip member, a
+// zlib header is a deflate method byte (usually 0x78) followed by a byte that
+// makes the pair a multiple of 31, anything else is taken to be raw deflate.
+// Raw data can look like a header by chance, so the other formats are tried in
+// turn when one fails with `DataError`, the first that works wins.
+pub fn decompress_auto(source: &[u8]) -> Result<Vec<u8>, ZlibError> {
+    // CM, the low 4 bits of the first byte, is 8 for deflate and CINFO, the
+    // window size, is at most 7.
+    let zlib_header = |cmf: u8, flg: u8| {
+        cmf & 0x0f == 8 && cmf >> 4 <= 7 && u16::from_be_bytes([cmf, flg]).is_multiple_of(31)
+    };
+    let detected = match source {
+        [0x1f, 0x8b, ..] => Format::Gzip,
+        [cmf, flg, ..] if zlib_header(*cmf, *flg) => Format::Zlib,
+        _ => Format::Raw,
+    };
+    let mut formats = vec![detected];
+    for format in [Format::Gzip, Format::Zlib, Format::Raw] {
+        if format != detected {
+            formats.push(format);
+        }
+    }
+    for format in formats {
+        match inflate_to_vec(format, source) {
+            Err(ZlibError::DataError) => continue,
+            result => return result,
+        }
+    }
+    Err(ZlibError::DataError)
+}
+
+// Decompresses all of `source` as `format`. Only gzip can have several
+// members back to back, anything after the end of a zlib or raw stream is a
+// `DataError`.
+fn inflate_to_vec(format: Format, source: &[u8]) -> Result<Vec<u8>, ZlibError> {
+    let mut inflater = Inflater::new(format)?;
+    let mut dest = Vec::new();
+    let mut buffer = [0u8; 0x8000];
+    let mut input = source;
+    loop {
+        let (consumed, produced, done) = inflater.decompress(input, &mut buffer)?;
+        dest.extend_from_slice(&buffer[..produced]);
+        input = &input[consumed..];
+        if done {
+            if input.is_empty() {
+                return Ok(dest);
+            }
+            if format != Format::Gzip {
+                return Err(ZlibError::DataError);
+            }
+            inflater.reset()?;
+        } else if consumed == 0 && produced == 0 {
+            return Err(ZlibError::DataError);
+        }
+    }
+}
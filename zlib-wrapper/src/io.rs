@@ -0,0 +1,547 @@
+use std::io::{Read, Write};
+use std::path::Path;
+
+use crate::compress::CompressionLevel;
+use crate::deflate::{DeflateOptions, Deflater, Z_SYNC_FLUSH};
+use crate::error::ZlibError;
+use crate::header::{GzipHeader, GzipHeaderBuilder};
+use crate::inflate::Inflater;
+use crate::stream::Format;
+
+// Compresses everything written to it, writing the compressed data to `inner`
+// as it goes. The shared part of `GzEncoder` and `ZlibEncoder`.
+struct DeflateWriter<W: Write> {
+    // Only `None` once `finish` took it.
+    inner: Option<W>,
+    deflater: Deflater,
+    buffer: Vec<u8>,
+}
+
+impl<W: Write> DeflateWriter<W> {
+    fn new(inner: W, deflater: Deflater) -> DeflateWriter<W> {
+        DeflateWriter {
+            inner: Some(inner),
+            deflater,
+            buffer: vec![0; 0x8000],
+        }
+    }
+
+    fn get_ref(&self) -> &W {
+        self.inner.as_ref().unwrap()
+    }
+
+    fn get_mut(&mut self) -> &mut W {
+        self.inner.as_mut().unwrap()
+    }
+
+    fn finish(mut self) -> std::io::Result<W> {
+        self.write_trailer()?;
+        Ok(self.inner.take().unwrap())
+    }
+
+    fn write_trailer(&mut self) -> std::io::Result<()> {
+        let inner = self.inner.as_mut().unwrap();
+        loop {
+            let (produced, done) = self.deflater.finish(&mut self.buffer)?;
+            inner.write_all(&self.buffer[..produced])?;
+            if done {
+                return Ok(());
+            }
+        }
+    }
+}
+
+impl<W: Write> std::io::Write for DeflateWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let inner = self.inner.as_mut().unwrap();
+        loop {
+            let (consumed, produced) = self.deflater.compress(buf, &mut self.buffer)?;
+            inner.write_all(&self.buffer[..produced])?;
+            // zlib only stops short of consuming anything when the output is full.
+            if consumed > 0 || buf.is_empty() {
+                return Ok(consumed);
+            }
+            if produced == 0 {
+                return Err(ZlibError::BufError.into());
+            }
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        let inner = self.inner.as_mut().unwrap();
+        loop {
+            let (_, produced, _) = self.deflater.deflate(&[], &mut self.buffer, Z_SYNC_FLUSH)?;
+            inner.write_all(&self.buffer[..produced])?;
+            // The flush is complete once zlib leaves room in the output.
+            if produced < self.buffer.len() {
+                break;
+            }
+        }
+        inner.flush()
+    }
+}
+
+impl<W: Write> Drop for DeflateWriter<W> {
+    // Finishes the stream if `finish` wasn't called, ignoring errors.
+    fn drop(&mut self) {
+        if self.inner.is_some() {
+            let _ = self.write_trailer();
+        }
+    }
+}
+
+// Compresses everything written to it into a gzip member, writing that to
+// `inner` as it goes, e.g. straight into a socket or an upload buffer.
+// Dropping it without `finish` still writes the trailer, but ignores errors.
+pub struct GzEncoder<W: Write> {
+    writer: DeflateWriter<W>,
+}
+
+impl<W: Write> GzEncoder<W> {
+    pub fn new(inner: W, level: CompressionLevel) -> Result<GzEncoder<W>, ZlibError> {
+        GzEncoder::with_options(inner, level, DeflateOptions::default())
+    }
+
+    // Same as `new`, tuned with `options`, e.g. a `Strategy` suiting the data.
+    pub fn with_options(
+        inner: W,
+        level: CompressionLevel,
+        options: DeflateOptions,
+    ) -> Result<GzEncoder<W>, ZlibError> {
+        let deflater = Deflater::with_options(level, Format::Gzip, options)?;
+        Ok(GzEncoder {
+            writer: DeflateWriter::new(inner, deflater),
+        })
+    }
+
+    // Same as `new`, with `header` in place of the bare gzip header.
+    pub fn with_header(
+        inner: W,
+        level: CompressionLevel,
+        header: &GzipHeader,
+    ) -> Result<GzEncoder<W>, ZlibError> {
+        let mut deflater = Deflater::new(level, Format::Gzip)?;
+        deflater.set_header(header)?;
+        Ok(GzEncoder {
+            writer: DeflateWriter::new(inner, deflater),
+        })
+    }
+
+    pub fn get_ref(&self) -> &W {
+        self.writer.get_ref()
+    }
+
+    // Writing to the inner writer directly corrupts the gzip output.
+    pub fn get_mut(&mut self) -> &mut W {
+        self.writer.get_mut()
+    }
+
+    // Same as `Deflater::set_on_progress`, the totals count the bytes written
+    // to the encoder and from it to the inner writer.
+    pub fn set_on_progress(&mut self, on_progress: Option<Box<dyn FnMut(u64, u64) + Send>>) {
+        self.writer.deflater.set_on_progress(on_progress);
+    }
+
+    // Writes the rest of the compressed data and the gzip trailer,
+    // then hands back the inner writer.
+    pub fn finish(self) -> std::io::Result<W> {
+        self.writer.finish()
+    }
+}
+
+impl<W: Write> std::io::Write for GzEncoder<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.writer.write(buf)
+    }
+
+    // Compresses everything written so far, so the inner writer receives
+    // data that decompresses up to this point.
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+// Same as `GzEncoder`, but writes a zlib stream, as HTTP `Content-Encoding: deflate`
+// and many protocols expect.
+pub struct ZlibEncoder<W: Write> {
+    writer: DeflateWriter<W>,
+}
+
+impl<W: Write> ZlibEncoder<W> {
+    pub fn new(inner: W, level: CompressionLevel) -> Result<ZlibEncoder<W>, ZlibError> {
+        ZlibEncoder::with_options(inner, level, DeflateOptions::default())
+    }
+
+    // Same as `new`, tuned with `options`, e.g. a `Strategy` suiting the data.
+    pub fn with_options(
+        inner: W,
+        level: CompressionLevel,
+        options: DeflateOptions,
+    ) -> Result<ZlibEncoder<W>, ZlibError> {
+        let deflater = Deflater::with_options(level, Format::Zlib, options)?;
+        Ok(ZlibEncoder {
+            writer: DeflateWriter::new(inner, deflater),
+        })
+    }
+
+    pub fn get_ref(&self) -> &W {
+        self.writer.get_ref()
+    }
+
+    // Writing to the inner writer directly corrupts the zlib output.
+    pub fn get_mut(&mut self) -> &mut W {
+        self.writer.get_mut()
+    }
+
+    // Same as `Deflater::set_on_progress`, the totals count the bytes written
+    // to the encoder and from it to the inner writer.
+    pub fn set_on_progress(&mut self, on_progress: Option<Box<dyn FnMut(u64, u64) + Send>>) {
+        self.writer.deflater.set_on_progress(on_progress);
+    }
+
+    // Writes the rest of the compressed data and the Adler-32 trailer,
+    // then hands back the inner writer.
+    pub fn finish(self) -> std::io::Result<W> {
+        self.writer.finish()
+    }
+}
+
+impl<W: Write> std::io::Write for ZlibEncoder<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.writer.write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+// Decompresses a single stream read from `inner`, or one after another until
+// `inner` ends if `multi` is set. The shared part of `GzDecoder`,
+// `MultiGzDecoder` and `ZlibDecoder`.
+struct InflateReader<R: Read> {
+    inner: R,
+    inflater: Inflater,
+    // Compressed bytes read from `inner`, `buffer[pos..len]` are not inflated yet.
+    buffer: Vec<u8>,
+    pos: usize,
+    len: usize,
+    done: bool,
+    multi: bool,
+    // The limit over all the streams, the inflater's only counts the current
+    // one, on top of what the ones before it decompressed to.
+    max_output: Option<u64>,
+    earlier_out: u64,
+}
+
+impl<R: Read> InflateReader<R> {
+    fn new(inner: R, inflater: Inflater, multi: bool) -> InflateReader<R> {
+        InflateReader {
+            inner,
+            inflater,
+            buffer: vec![0; 0x8000],
+            pos: 0,
+            len: 0,
+            done: false,
+            multi,
+            max_output: None,
+            earlier_out: 0,
+        }
+    }
+
+    fn set_max_output(&mut self, max_output: Option<u64>) {
+        self.max_output = max_output;
+        let left = max_output.map(|max| max.saturating_sub(self.earlier_out));
+        self.inflater.set_max_output(left);
+    }
+
+    // After a stream ended, whether another one follows in `inner`.
+    fn next_stream(&mut self) -> std::io::Result<bool> {
+        if !self.multi {
+            return Ok(false);
+        }
+        if self.pos == self.len {
+            self.len = self.inner.read(&mut self.buffer)?;
+            self.pos = 0;
+            if self.len == 0 {
+                return Ok(false);
+            }
+        }
+        self.earlier_out += self.inflater.total_out();
+        self.inflater.reset()?;
+        self.set_max_output(self.max_output);
+        self.done = false;
+        Ok(true)
+    }
+}
+
+impl<R: Read> std::io::Read for InflateReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        loop {
+            if self.done && !self.next_stream()? {
+                return Ok(0);
+            }
+            if self.pos == self.len {
+                // The inner reader may return less than asked, that's fine.
+                self.len = self.inner.read(&mut self.buffer)?;
+                self.pos = 0;
+                if self.len == 0 {
+                    return Err(std::io::ErrorKind::UnexpectedEof.into());
+                }
+            }
+            let input = &self.buffer[self.pos..self.len];
+            let (consumed, produced, done) = self.inflater.decompress(input, buf)?;
+            self.pos += consumed;
+            self.done = done;
+            if produced > 0 {
+                return Ok(produced);
+            }
+            if consumed == 0 && !done {
+                return Err(ZlibError::DataError.into());
+            }
+        }
+    }
+}
+
+// Decompresses a gzip member read from `inner`, which can be any reader,
+// not only a file. Reads end with the member, whatever follows it in `inner`:
+// of a file with several members, e.g. `cat a.gz b.gz` or `compress_parallel`
+// output, only the first is read, `MultiGzDecoder` reads them all.
+pub struct GzDecoder<R: Read> {
+    reader: InflateReader<R>,
+}
+
+impl<R: Read> GzDecoder<R> {
+    pub fn new(inner: R) -> Result<GzDecoder<R>, ZlibError> {
+        let mut inflater = Inflater::new(Format::Gzip)?;
+        inflater.track_header()?;
+        Ok(GzDecoder {
+            reader: InflateReader::new(inner, inflater, false),
+        })
+    }
+
+    // Same as `Inflater::set_max_output`, reads fail with `LimitExceeded`.
+    pub fn set_max_output(&mut self, max_output: Option<u64>) {
+        self.reader.set_max_output(max_output);
+    }
+
+    // Same as `Inflater::set_max_ratio`, reads fail with `LimitExceeded`.
+    pub fn set_max_ratio(&mut self, max_ratio: Option<u64>) {
+        self.reader.inflater.set_max_ratio(max_ratio);
+    }
+
+    // The header of the member, once enough of it has been read.
+    pub fn header(&self) -> Option<GzipHeader> {
+        self.reader.inflater.header()
+    }
+
+    pub fn get_ref(&self) -> &R {
+        &self.reader.inner
+    }
+
+    // Reading from the inner reader directly corrupts the decompressed output.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.reader.inner
+    }
+
+    // Compressed bytes already read from the inner reader but not decompressed
+    // are lost, including anything past the end of the member.
+    pub fn into_inner(self) -> R {
+        self.reader.inner
+    }
+}
+
+impl<R: Read> std::io::Read for GzDecoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.reader.read(buf)
+    }
+}
+
+// Same as `GzDecoder`, but reads every gzip member in `inner` one after another,
+// until it ends, like gzip(1) and `gzip_decompress`. Anything but another member
+// after one fails with `DataError`.
+pub struct MultiGzDecoder<R: Read> {
+    reader: InflateReader<R>,
+}
+
+impl<R: Read> MultiGzDecoder<R> {
+    pub fn new(inner: R) -> Result<MultiGzDecoder<R>, ZlibError> {
+        let mut inflater = Inflater::new(Format::Gzip)?;
+        inflater.track_header()?;
+        Ok(MultiGzDecoder {
+            reader: InflateReader::new(inner, inflater, true),
+        })
+    }
+
+    // Same as `Inflater::set_max_output`, over all the members together.
+    pub fn set_max_output(&mut self, max_output: Option<u64>) {
+        self.reader.set_max_output(max_output);
+    }
+
+    // Same as `Inflater::set_max_ratio`, for each member.
+    pub fn set_max_ratio(&mut self, max_ratio: Option<u64>) {
+        self.reader.inflater.set_max_ratio(max_ratio);
+    }
+
+    // The header of the member being read, once enough of it has been read.
+    pub fn header(&self) -> Option<GzipHeader> {
+        self.reader.inflater.header()
+    }
+
+    pub fn get_ref(&self) -> &R {
+        &self.reader.inner
+    }
+
+    // Reading from the inner reader directly corrupts the decompressed output.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.reader.inner
+    }
+
+    // Compressed bytes already read from the inner reader but not decompressed
+    // are lost.
+    pub fn into_inner(self) -> R {
+        self.reader.inner
+    }
+}
+
+impl<R: Read> std::io::Read for MultiGzDecoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.reader.read(buf)
+    }
+}
+
+// Same as `GzDecoder`, but reads a zlib stream.
+pub struct ZlibDecoder<R: Read> {
+    reader: InflateReader<R>,
+}
+
+impl<R: Read> ZlibDecoder<R> {
+    pub fn new(inner: R) -> Result<ZlibDecoder<R>, ZlibError> {
+        let inflater = Inflater::new(Format::Zlib)?;
+        Ok(ZlibDecoder {
+            reader: InflateReader::new(inner, inflater, false),
+        })
+    }
+
+    // Same as `Inflater::set_max_output`, reads fail with `LimitExceeded`.
+    pub fn set_max_output(&mut self, max_output: Option<u64>) {
+        self.reader.set_max_output(max_output);
+    }
+
+    // Same as `Inflater::set_max_ratio`, reads fail with `LimitExceeded`.
+    pub fn set_max_ratio(&mut self, max_ratio: Option<u64>) {
+        self.reader.inflater.set_max_ratio(max_ratio);
+    }
+
+    pub fn get_ref(&self) -> &R {
+        &self.reader.inner
+    }
+
+    // Reading from the inner reader directly corrupts the decompressed output.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.reader.inner
+    }
+
+    // Compressed bytes already read from the inner reader but not decompressed
+    // are lost, including anything past the end of the stream.
+    pub fn into_inner(self) -> R {
+        self.reader.inner
+    }
+}
+
+impl<R: Read> std::io::Read for ZlibDecoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.reader.read(buf)
+    }
+}
+
+// Compresses the file at `input` into a gzip file at `output`, piece by piece,
+// so the file never has to fit in memory. Like gzip(1), the header keeps the
+// file name and modification time of `input`.
+pub fn compress_file(
+    input: &Path,
+    output: &Path,
+    level: CompressionLevel,
+) -> Result<(), ZlibError> {
+    let mut source = std::fs::File::open(input)?;
+    let header = GzipHeader::for_file(input, &source)?;
+    let dest = std::fs::File::create(output)?;
+    let mut encoder = GzEncoder::with_header(dest, level, &header)?;
+    std::io::copy(&mut source, &mut encoder)?;
+    encoder.finish()?;
+    Ok(())
+}
+
+// Same as `compress_file`, but the file is mapped into memory and compressed
+// straight from there, without copying it through read calls. Fails with
+// `DataError` if the file changed size while it was being compressed, the
+// output is then a mix of old and new contents.
+//
+// A mapping is only as stable as the file under it: writes to `input` by
+// another process show up in the middle of compressing it. If another process
+// truncates `input` meanwhile, this process is killed by SIGBUS, there is no
+// error to return. Only use it on files nobody else is writing to.
+#[cfg(feature = "mmap")]
+pub fn compress_file_mmap(
+    input: &Path,
+    output: &Path,
+    level: CompressionLevel,
+) -> Result<(), ZlibError> {
+    let source = std::fs::File::open(input)?;
+    let header = GzipHeader::for_file(input, &source)?;
+    // Unsafe because of the above, nothing in this process writes to it.
+    let map = unsafe { memmap2::Mmap::map(&source)? };
+    let dest = std::fs::File::create(output)?;
+    let mut encoder = GzEncoder::with_header(dest, level, &header)?;
+    encoder.write_all(&map)?;
+    encoder.finish()?;
+    // The mapping covers the size the file had when it was mapped.
+    if source.metadata()?.len() != map.len() as u64 {
+        return Err(ZlibError::DataError);
+    }
+    Ok(())
+}
+
+impl GzipHeader {
+    // The header `compress_file` writes for the file at `path`, already open as
+    // `file`: its name and modification time, like gzip(1).
+    pub fn for_file(path: &Path, file: &std::fs::File) -> Result<GzipHeader, ZlibError> {
+        let mut header = GzipHeaderBuilder::new();
+        if let Some(name) = path.file_name() {
+            header = header.name(name.to_string_lossy());
+        }
+        // Unknown, before 1970 or after 2106 is stored as 0, meaning no time.
+        let mtime = file
+            .metadata()?
+            .modified()
+            .ok()
+            .and_then(|modified| modified.duration_since(std::time::UNIX_EPOCH).ok())
+            .and_then(|since_epoch| u32::try_from(since_epoch.as_secs()).ok());
+        header = header.mtime(mtime.unwrap_or(0));
+        Ok(header.build())
+    }
+}
+
+// Gzips everything read from `src` into `dst`, e.g. from a socket to a pipe.
+// Returns how many uncompressed bytes were read from `src`.
+pub fn compress_stream<R: Read, W: Write>(
+    mut src: R,
+    dst: W,
+    level: CompressionLevel,
+) -> Result<u64, ZlibError> {
+    let mut encoder = GzEncoder::new(dst, level)?;
+    let copied = std::io::copy(&mut src, &mut encoder)?;
+    encoder.finish()?;
+    Ok(copied)
+}
+
+// Decompresses the gzip data read from `src` into `dst`, every member of it,
+// the counterpart of `compress_stream`. Returns how many uncompressed bytes
+// were written to `dst`.
+pub fn decompress_stream<R: Read, W: Write>(src: R, mut dst: W) -> Result<u64, ZlibError> {
+    let mut decoder = MultiGzDecoder::new(src)?;
+    Ok(std::io::copy(&mut decoder, &mut dst)?)
+}
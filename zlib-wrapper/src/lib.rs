@@ -0,0 +1,59 @@
+// Safe bindings to the system zlib: one-shot and streaming compression in the
+// zlib, gzip and raw deflate formats, checksums, and gz files.
+//
+// The one-shot functions, checksums and streams only need `alloc` and zlib
+// itself. Without the `std` feature only those are built, for targets that link
+// a static zlib and have no operating system. The gz file and `std::io` parts
+// need `std`.
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+#[cfg(feature = "async")]
+mod async_io;
+mod checksum;
+mod compress;
+mod deflate;
+mod error;
+#[cfg(feature = "std")]
+mod gz;
+mod header;
+mod inflate;
+#[cfg(feature = "std")]
+mod io;
+mod stream;
+mod version;
+
+#[cfg(feature = "async")]
+pub use async_io::{AsyncGzDecoder, AsyncGzEncoder, AsyncMultiGzDecoder};
+pub use checksum::{Adler32, Crc32, adler32, adler32_combine, crc32, crc32_combine};
+pub use compress::{
+    CompressionLevel, compress_bound, zlib_compress, zlib_compress_cow, zlib_compress_into,
+    zlib_compress_level, zlib_store, zlib_uncompress, zlib_uncompress_auto,
+    zlib_uncompress_limited,
+};
+#[cfg(feature = "std")]
+pub use deflate::compress_parallel;
+pub use deflate::{
+    DeflateOptions, Deflater, FlushMode, Strategy, gzip_compress, gzip_compress_with_header,
+};
+pub use error::ZlibError;
+#[cfg(feature = "std")]
+pub use gz::{
+    GzFile, GzFileState, GzHandle, GzItem, GzLines, GzMode, GzReader, GzWriter, decompress_file,
+    gz_lines, gz_open, read_gz_file, read_gz_file_bytes, read_gz_file_with_capacity,
+};
+pub use header::{GzipHeader, GzipHeaderBuilder};
+pub use inflate::{
+    DEFAULT_MAX_RATIO, Inflater, decompress_auto, gzip_decompress, gzip_decompress_with_header,
+    gzip_verify,
+};
+#[cfg(feature = "mmap")]
+pub use io::compress_file_mmap;
+#[cfg(feature = "std")]
+pub use io::{
+    GzDecoder, GzEncoder, MultiGzDecoder, ZlibDecoder, ZlibEncoder, compress_file, compress_stream,
+    decompress_stream,
+};
+pub use stream::{Allocator, Format};
+pub use version::{CompileFlags, check_zlib_version, compile_flags, zlib_version};
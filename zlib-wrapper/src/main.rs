@@ -1,183 +1,215 @@
-use std::ffi::CString;
-
-use libc::{c_char, c_int, c_uchar, c_uint, c_ulong};
-
-// The link attribute tells `rustc` that we need to link these functions to zlib.
+// A small gzip tool on top of the library:
 //
-// This is equivalent to adding `-lz` flag at link time.
-// Instructs rustc that these functions belong to the external "z" library.
+//     brenden compress <in> <out>
+//     brenden decompress <in> <out>
+//     brenden crc <file>
 //
-// Can verify this by running:
-// on macOS: `otool -L target/debug/zlib-wrapper`,
-// on Linux: `ldd`
-// on Windows: `dumpbin`
-#[link(name = "z")]
-unsafe extern "C" {
-    // The biggest challenge is mapping verious types and functions from C to Rust + libc crate.
-    // The rust-bindgen tool can generate bindings to C libraries automatically from C headers.
-    //
-    // But sometimes dealing with rust-bindgen is not worth the trouble for simple cases.
-    // In those cases we can:
-    // 1. Copy the C struct definition.
-    // 2. Convert the C types to Rust types.
-    // 3. Implement function interfaaces.
-
-    // --------------------------------------------------------------------------
-    // Definitions from zlib.h
-    // --------------------------------------------------------------------------
-    //
-    // #ifndef ZEXTERN
-    // #  define ZEXTERN extern
-    // #endif
-    // #ifndef ZEXPORT
-    // #  define ZEXPORT
-    // #endif
-    //
-    // typedef unsigned char  Byte;  /* 8 bits */
-    // typedef unsigned int   uInt;  /* 16 bits or more */
-    // typedef unsigned long  uLong; /* 32 bits or more */
-    //
-    // typedef Byte  FAR Bytef;
-    // typedef char  FAR charf;
-    // typedef int   FAR intf;
-    // typedef uInt  FAR uIntf;
-    // typedef uLong FAR uLongf;
-    //
-    // ZEXTERN int ZEXPORT compress OF((Bytef *dest,   uLongf *destLen,
-    //                                  const Bytef *source, uLong sourceLen));
-    //
-    // ZEXTERN uLong ZEXPORT compressBound OF((uLong sourceLen));
-    //
-    // ZEXTERN int ZEXPORT uncompress OF((Bytef *dest,   uLongf *destLen,
-    //                                    const Bytef *source, uLong sourceLen));
-    // --------------------------------------------------------------------------
-
-    unsafe fn compress(
-        dest: *mut u8,
-        dest_len: *mut c_ulong,
-        source: *const u8,
-        source_len: c_ulong,
-    ) -> c_int;
-
-    // Estimates the size of buffer required to
-    // compress `source_len` bytes of data using the compress()
-    unsafe fn compressBound(source_len: c_ulong) -> c_ulong;
-
-    unsafe fn uncompress(
-        dest: *mut u8,
-        dest_len: *mut c_ulong,
-        source: *const u8,
-        source_len: c_ulong,
-    ) -> c_int;
-}
-
-pub fn zlip_compress(source: &[u8]) -> Vec<u8> {
-    unsafe {
-        let source_len = source.len() as c_ulong;
-
-        let mut dest_len = compressBound(source_len);
-        let mut dest = Vec::with_capacity(dest_len as usize);
-
-        compress(
-            dest.as_mut_ptr(),
-            &mut dest_len,
-            source.as_ptr(),
-            source_len,
-        );
-        dest.set_len(dest_len as usize);
-        dest
+// `-` in place of a path reads stdin or writes stdout.
+use std::ffi::OsString;
+use std::fs::File;
+use std::io::{BufWriter, IsTerminal, Read, Write};
+use std::path::Path;
+use std::process::ExitCode;
+
+#[cfg(unix)]
+use zlib_wrapper::GzMode;
+#[cfg(not(unix))]
+use zlib_wrapper::MultiGzDecoder;
+use zlib_wrapper::{
+    CompressionLevel, Crc32, GzEncoder, GzReader, GzipHeader, ZlibError, compress_stream,
+};
+
+const USAGE: &str = "usage: brenden compress <in> <out>
+       brenden decompress <in> <out>
+       brenden crc <file>
+
+A path of - reads stdin or writes stdout.";
+
+fn main() -> ExitCode {
+    // `std::env::args` panics on an argument that isn't UTF-8. The paths have
+    // to be, `GzReader::open` takes a `&str`.
+    let args: Result<Vec<String>, OsString> = std::env::args_os()
+        .skip(1)
+        .map(OsString::into_string)
+        .collect();
+    let args = match args {
+        Ok(args) => args,
+        Err(arg) => {
+            eprintln!("brenden: {}: {}", arg.to_string_lossy(), NOT_UTF8);
+            return ExitCode::from(2);
+        }
+    };
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    let result = match args[..] {
+        ["compress", input, output] => compress(input, output),
+        ["decompress", input, output] => decompress(input, output),
+        ["crc", input] => crc(input),
+        _ => {
+            eprintln!("{}", USAGE);
+            return ExitCode::from(2);
+        }
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err((path, err)) => {
+            eprintln!("brenden: {}: {}", path, err);
+            ExitCode::FAILURE
+        }
     }
 }
 
-pub fn zlib_uncompress(source: &[u8], max_dest_len: usize) -> Vec<u8> {
-    unsafe {
-        let source_len = source.len() as c_ulong;
+// The path an error is about, to tell the input from the output.
+// `std::io::Error` as most failures are the OS's, a `ZlibError` converts into one.
+type CliResult<'a> = Result<(), (&'a str, std::io::Error)>;
 
-        let mut dest_len = max_dest_len as c_ulong;
-        let mut dest = Vec::with_capacity(max_dest_len);
-
-        uncompress(
-            dest.as_mut_ptr(),
-            &mut dest_len,
-            source.as_ptr(),
-            source_len,
-        );
-
-        dest.set_len(dest_len as usize);
-        dest
+fn compress<'a>(input: &'a str, output: &'a str) -> CliResult<'a> {
+    // Between files the header keeps the name and modification time, like gzip(1).
+    if input != "-" && output != "-" {
+        return compress_file(input, output);
+    }
+    if output == "-" && std::io::stdout().is_terminal() {
+        return Err((output, std::io::Error::other(NOT_TO_TERMINAL)));
     }
+    if input == "-" && output == "-" {
+        return compress_stdio().map_err(|err| (input, err.into()));
+    }
+    let mut source = open(input).map_err(|err| (input, err))?;
+    let dest = create(output).map_err(|err| (output, err))?;
+    let mut encoder =
+        GzEncoder::new(dest, CompressionLevel::Default).map_err(|err| (output, err.into()))?;
+    copy(&mut source, input, &mut encoder, output)?;
+    encoder.finish().map_err(|err| (output, err))?;
+    Ok(())
 }
 
-// Align memory in this struct as C compiler would.
-// A C struct representing zlib file state, as defined in zlib.h
-#[repr(C)]
-struct GzFileState {
-    have: c_uint,
-    next: *mut c_uchar,
-    pos: i64,
+const NOT_UTF8: &str = "not a valid UTF-8 path";
+
+// Like gzip(1), a terminal gets garbled by binary data, and on Windows the
+// console refuses to print what isn't UTF-8.
+const NOT_TO_TERMINAL: &str = "compressed data not written to a terminal";
+
+// stdin to stdout, e.g. `brenden compress - - < in > out`, as a gzip filter.
+// Rust never translates newlines, stdout is written byte for byte on Windows
+// as well, no binary mode to set.
+fn compress_stdio() -> Result<(), ZlibError> {
+    // stdout is line buffered, which would flush at every 0x0a in the output.
+    let mut stdout = BufWriter::with_capacity(0x10000, std::io::stdout().lock());
+    compress_stream(
+        std::io::stdin().lock(),
+        &mut stdout,
+        CompressionLevel::Default,
+    )?;
+    // Errors dropping a `BufWriter` are lost, e.g. a closed pipe.
+    stdout.flush()?;
+    Ok(())
 }
 
-type GzFile = *mut GzFileState;
-
-// Instructs rustc that these functions belong to the external "z" library.
-// Yes, "z" in the name of the "zlib" library.
-#[link(name = "z")]
-unsafe extern "C" {
-    // struct gzFile_s {
-    //     unsigned have;
-    //     unsigned char *next;
-    //     z_off64_t pos;
-    // };
-    //
-    // typedef struct gzFile_s *gzFile;
-    //
-    // ZEXTERN gzFile ZEXPORT gzopen OF((const char *, const char *));
-    // ZEXTERN int ZEXPORT gzread OF((gzFile file, voidp buf, unsigned len));
-    // ZEXTERN int ZEXPORT gzclose OF((gzFile file));
-    // ZEXTERN int ZEXPORT gzeof OF((gzFile file));
-
-    unsafe fn gzopen(path: *const c_char, mode: *const c_char) -> GzFile;
-    unsafe fn gzread(file: GzFile, buf: *mut c_uchar, len: c_uint) -> c_int;
-    unsafe fn gzclose(file: GzFile) -> c_int;
-    unsafe fn gzeof(file: GzFile) -> c_int;
+fn decompress<'a>(input: &'a str, output: &'a str) -> CliResult<'a> {
+    // gz files may hold several members, gzread handles them, for stdin too.
+    let mut source = open_gz(input).map_err(|err| (input, err))?;
+    if output != "-" {
+        let mut dest = File::create(output).map_err(|err| (output, err))?;
+        let copied = copy(&mut source, input, &mut dest, output);
+        // Like gzip(1), don't leave half a file behind, e.g. of a truncated input.
+        if copied.is_err() {
+            let _ = std::fs::remove_file(output);
+        }
+        return copied;
+    }
+    // stdout is line buffered, which would flush at every 0x0a in the output.
+    let mut dest = BufWriter::with_capacity(0x10000, std::io::stdout().lock());
+    copy(&mut source, input, &mut dest, output)?;
+    // Errors dropping a `BufWriter` are lost, e.g. a closed pipe.
+    dest.flush().map_err(|err| (output, err))
 }
 
-// Opens gzipped file, reads its contents, and returns them as a string.
-fn read_gz_file(name: &str) -> String {
-    let mut buffer = [0u8; 0x1000]; // 16^3 = 4096 bytes
-    let mut contents = String::new();
-    unsafe {
-        // Convert rust UTF-8 into an ASCII C-string
-        let c_name = CString::new(name).expect("CString failed");
-        let c_mode = CString::new("r").expect("CString failed");
-        let file = gzopen(c_name.as_ptr(), c_mode.as_ptr());
-        if file.is_null() {
-            panic!("Couldn't read file: {}", std::io::Error::last_os_error());
-        }
-        while gzeof(file) == 0 {
-            let bytes_read = gzread(file, buffer.as_mut_ptr(), (buffer.len() - 1) as c_uint);
-            let s = std::str::from_utf8(&buffer[..(bytes_read as usize)]).unwrap();
-            contents.push_str(s);
-        }
-        gzclose(file);
+// Between files, each opened on its own so an error names the file it's about,
+// `compress_file` can't tell which of its two paths failed.
+fn compress_file<'a>(input: &'a str, output: &'a str) -> CliResult<'a> {
+    let mut source = File::open(input).map_err(|err| (input, err))?;
+    let header =
+        GzipHeader::for_file(Path::new(input), &source).map_err(|err| (input, err.into()))?;
+    let dest = File::create(output).map_err(|err| (output, err))?;
+    let mut encoder = GzEncoder::with_header(dest, CompressionLevel::Default, &header)
+        .map_err(|err| (output, err.into()))?;
+    copy(&mut source, input, &mut encoder, output)?;
+    encoder.finish().map_err(|err| (output, err))?;
+    Ok(())
+}
 
-        contents
+// `std::io::copy`, with read errors reported against `input` and write errors
+// against `output`.
+fn copy<'a>(
+    source: &mut dyn Read,
+    input: &'a str,
+    dest: &mut dyn Write,
+    output: &'a str,
+) -> CliResult<'a> {
+    let mut buffer = vec![0u8; 0x10000];
+    loop {
+        let read = match source.read(&mut buffer) {
+            Ok(0) => return Ok(()),
+            Ok(read) => read,
+            Err(err) if err.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err((input, err)),
+        };
+        dest.write_all(&buffer[..read])
+            .map_err(|err| (output, err))?;
     }
 }
 
-fn main() {
-    println!("1. compress/decompress");
-
-    let hello_zlib = "hello, zlib, no exclamation mark".as_bytes();
-    let hello_zlib_compressed = zlip_compress(hello_zlib);
-    let hello_zlib_uncompressed = zlib_uncompress(&hello_zlib_compressed, 100);
+fn open_gz(path: &str) -> std::io::Result<Box<dyn Read>> {
+    // The reader closes stdin when it's done.
+    #[cfg(unix)]
+    let reader = if path == "-" {
+        GzReader::from_raw_fd(0, GzMode::Read)?
+    } else {
+        GzReader::open(path)?
+    };
+    // No file descriptor to hand to zlib, this only takes gzip data anyway.
+    #[cfg(not(unix))]
+    if path == "-" {
+        return Ok(Box::new(MultiGzDecoder::new(std::io::stdin().lock())?));
+    }
+    #[cfg(not(unix))]
+    let reader = GzReader::open(path)?;
+    // gzread passes anything that isn't gzipped through as is, gzip -d refuses.
+    if reader.is_direct() {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            NOT_GZIP,
+        ));
+    }
+    Ok(Box::new(reader))
+}
 
-    assert_eq!(hello_zlib, hello_zlib_uncompressed);
+const NOT_GZIP: &str = "not in gzip format";
 
-    let hello_zlib_utf8 = String::from_utf8(hello_zlib_uncompressed).expect("Invalid characters");
-    println!("{}", hello_zlib_utf8);
+fn crc(input: &str) -> CliResult<'_> {
+    let mut source = open(input).map_err(|err| (input, err))?;
+    let mut crc = Crc32::new();
+    let mut buffer = vec![0u8; 0x10000];
+    loop {
+        let read = source.read(&mut buffer).map_err(|err| (input, err))?;
+        if read == 0 {
+            break;
+        }
+        crc.update(&buffer[..read]);
+    }
+    println!("{:08x}  {}", crc.finalize(), input);
+    Ok(())
+}
 
-    println!("1. read_gz_file");
+fn open(path: &str) -> std::io::Result<Box<dyn Read>> {
+    if path == "-" {
+        return Ok(Box::new(std::io::stdin().lock()));
+    }
+    Ok(Box::new(File::open(path)?))
+}
 
-    println!("{}", read_gz_file("file.txt.gz"));
+fn create(path: &str) -> std::io::Result<Box<dyn Write>> {
+    if path == "-" {
+        return Ok(Box::new(std::io::stdout().lock()));
+    }
+    Ok(Box::new(File::create(path)?))
 }
@@ -0,0 +1,153 @@
+#[cfg(not(feature = "std"))]
+use alloc::boxed::Box;
+use alloc::sync::Arc;
+use core::ffi::c_void;
+use core::ptr;
+
+use libc::{c_char, c_int, c_uint, c_ulong};
+
+use crate::deflate::MAX_WBITS;
+
+// Allocation functions zlib calls to manage the stream's internal state.
+type AllocFunc =
+    unsafe extern "C" fn(opaque: *mut c_void, items: c_uint, size: c_uint) -> *mut c_void;
+type FreeFunc = unsafe extern "C" fn(opaque: *mut c_void, address: *mut c_void);
+
+// Where a `Deflater` or `Inflater` gets the memory for its internal state,
+// e.g. a fixed pool or an arena, instead of malloc/free.
+// zlib calls it from C, so neither method may panic, a panic aborts the process.
+// A stream can move to another thread and its copies allocate through the
+// same allocator, hence `Send + Sync`.
+pub trait Allocator: Send + Sync {
+    // Returns `size` bytes suitably aligned for any type, or null if there's no memory.
+    fn alloc(&self, size: usize) -> *mut c_void;
+    // Frees memory `alloc` returned, zlib only passes pointers it got from `alloc`
+    // and didn't free yet. Keep track of the size if you need it, zlib doesn't pass it back.
+    fn free(&self, ptr: *mut c_void);
+}
+
+// Boxed inside the `Arc`, so `opaque` can be a thin pointer to the box.
+// A stream refers to it until it's ended, so it must be dropped after that.
+// Shared, as copies of a stream keep allocating through the same one.
+pub(crate) type SharedAllocator = Arc<Box<dyn Allocator>>;
+
+unsafe extern "C" fn alloc_trampoline(
+    opaque: *mut c_void,
+    items: c_uint,
+    size: c_uint,
+) -> *mut c_void {
+    let allocator = unsafe { &*opaque.cast::<Box<dyn Allocator>>() };
+    match (items as usize).checked_mul(size as usize) {
+        Some(size) => allocator.alloc(size),
+        None => ptr::null_mut(),
+    }
+}
+
+unsafe extern "C" fn free_trampoline(opaque: *mut c_void, address: *mut c_void) {
+    let allocator = unsafe { &*opaque.cast::<Box<dyn Allocator>>() };
+    allocator.free(address)
+}
+
+// A C struct representing a compression/decompression stream, as defined in zlib.h
+#[repr(C)]
+pub(crate) struct ZStream {
+    pub(crate) next_in: *const u8,
+    pub(crate) avail_in: c_uint,
+    pub(crate) total_in: c_ulong,
+
+    pub(crate) next_out: *mut u8,
+    pub(crate) avail_out: c_uint,
+    pub(crate) total_out: c_ulong,
+
+    pub(crate) msg: *const c_char,
+    state: *mut c_void,
+
+    // Null function pointers make zlib fall back to malloc/free.
+    zalloc: Option<AllocFunc>,
+    zfree: Option<FreeFunc>,
+    opaque: *mut c_void,
+
+    data_type: c_int,
+    pub(crate) adler: c_ulong,
+    reserved: c_ulong,
+}
+
+impl ZStream {
+    pub(crate) fn new() -> ZStream {
+        ZStream {
+            next_in: ptr::null(),
+            avail_in: 0,
+            total_in: 0,
+            next_out: ptr::null_mut(),
+            avail_out: 0,
+            total_out: 0,
+            msg: ptr::null(),
+            state: ptr::null_mut(),
+            zalloc: None,
+            zfree: None,
+            opaque: ptr::null_mut(),
+            data_type: 0,
+            adler: 0,
+            reserved: 0,
+        }
+    }
+
+    // A stream that allocates through `allocator`, if given, instead of malloc/free.
+    pub(crate) fn with_allocator(allocator: Option<&SharedAllocator>) -> ZStream {
+        let mut stream = ZStream::new();
+        if let Some(allocator) = allocator {
+            stream.zalloc = Some(alloc_trampoline);
+            stream.zfree = Some(free_trampoline);
+            stream.opaque = ptr::from_ref::<Box<dyn Allocator>>(allocator)
+                .cast_mut()
+                .cast();
+        }
+        stream
+    }
+
+    // Points the stream at the given buffers and returns how many bytes
+    // of each it can use, zlib counts them in C unsigned ints.
+    pub(crate) fn set_buffers(&mut self, input: &[u8], output: &mut [u8]) -> (c_uint, c_uint) {
+        let avail_in = input.len().min(c_uint::MAX as usize) as c_uint;
+        let avail_out = output.len().min(c_uint::MAX as usize) as c_uint;
+        self.next_in = input.as_ptr();
+        self.avail_in = avail_in;
+        self.next_out = output.as_mut_ptr();
+        self.avail_out = avail_out;
+        (avail_in, avail_out)
+    }
+
+    // Forgets the buffers, so zlib is never left holding dangling pointers.
+    pub(crate) fn clear_buffers(&mut self) {
+        self.next_in = ptr::null();
+        self.avail_in = 0;
+        self.next_out = ptr::null_mut();
+        self.avail_out = 0;
+    }
+}
+
+// The wire format of a compressed stream, pick it explicitly for every
+// `Deflater`/`Inflater`. zlib picks it based on the sign and offset of `windowBits`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    // A 2-byte header, the deflate data and an Adler-32 trailer, as `zlib_compress` produces.
+    Zlib,
+    // A gzip member: a header, the deflate data and a CRC-32/size footer, as in .gz files.
+    Gzip,
+    // Bare deflate data with no header or trailer, as used by PNG, zip and HTTP `deflate`.
+    Raw,
+    // Either `Gzip` or `Zlib`, detected from the header. Decompression only,
+    // `Deflater::new` fails with `StreamError` for it.
+    GzipOrZlib,
+}
+
+impl Format {
+    pub(crate) fn window_bits(self) -> c_int {
+        match self {
+            Format::Zlib => MAX_WBITS,
+            Format::Gzip => MAX_WBITS + 16,
+            Format::Raw => -MAX_WBITS,
+            Format::GzipOrZlib => MAX_WBITS + 32,
+        }
+    }
+}
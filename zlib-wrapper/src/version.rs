@@ -0,0 +1,107 @@
+#[cfg(not(feature = "std"))]
+use alloc::string::String;
+use core::ffi::CStr;
+
+use libc::{c_char, c_ulong};
+
+use crate::error::ZlibError;
+
+#[cfg_attr(not(any(feature = "bundled", feature = "zlib-ng")), link(name = "z"))]
+unsafe extern "C" {
+    // ZEXTERN const char * ZEXPORT zlibVersion OF((void));
+    //
+    // ZEXTERN uLong ZEXPORT zlibCompileFlags OF((void));
+
+    // The version of the zlib library the binary is actually linked against.
+    unsafe fn zlibVersion() -> *const c_char;
+
+    // The options the linked zlib library was built with, as a bitfield.
+    unsafe fn zlibCompileFlags() -> c_ulong;
+}
+
+// The version of the linked zlib library, e.g. "1.2.13".
+pub fn zlib_version() -> String {
+    // zlibVersion returns a pointer to a static string, it must not be freed.
+    let version = unsafe { CStr::from_ptr(zlibVersion()) };
+    version.to_string_lossy().into_owned()
+}
+
+// The version of zlib.h the definitions in this file are copied from.
+// zlib's init functions compare it against the linked library to detect ABI mismatches.
+pub(crate) const ZLIB_VERSION: &CStr = c"1.2.13";
+
+// Checks the linked zlib library is compatible with the zlib.h this file mirrors.
+// Fails with `VersionError` if the major versions differ, e.g. when a system
+// upgrade swapped libz under a previously built binary.
+pub fn check_zlib_version() -> Result<(), ZlibError> {
+    let linked = zlib_version();
+    let header = ZLIB_VERSION.to_str().expect("ZLIB_VERSION is ASCII");
+    if linked.split('.').next() != header.split('.').next() {
+        return Err(ZlibError::VersionError);
+    }
+    Ok(())
+}
+
+// The raw bitfield of options the linked zlib was built with,
+// `CompileFlags::from` decodes it.
+pub fn compile_flags() -> u64 {
+    unsafe { zlibCompileFlags() as u64 }
+}
+
+// The options the linked zlib was built with, decoded from `compile_flags`.
+// Type sizes that don't match libc's are an ABI mismatch, which shows up as
+// corrupted data or crashes in this file's FFI declarations.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CompileFlags {
+    // Sizes in bits of uInt, uLong, voidpf and z_off_t, `None` if not 16, 32 or 64.
+    pub uint_bits: Option<u32>,
+    pub ulong_bits: Option<u32>,
+    pub pointer_bits: Option<u32>,
+    pub z_off_bits: Option<u32>,
+    // Built with ZLIB_DEBUG, which checks internal consistency and is slow.
+    pub debug: bool,
+    // Uses assembler code.
+    pub asm: bool,
+    // Exported functions use the WINAPI calling convention instead of C.
+    pub winapi: bool,
+    // The decoding tables and CRC table are built on first use, which isn't
+    // thread-safe until they're built.
+    pub build_fixed: bool,
+    pub dynamic_crc_table: bool,
+    // The gz* functions can write compressed data, false with NO_GZCOMPRESS.
+    pub gz_compress: bool,
+    // The streams can read and write the gzip format, false with NO_GZIP.
+    pub gzip: bool,
+    // Built with PKZIP_BUG_WORKAROUND, inflate is slightly more permissive.
+    pub pkzip_bug_workaround: bool,
+    // Built with FASTEST, deflate only has the lowest compression level.
+    pub fastest: bool,
+}
+
+impl From<u64> for CompileFlags {
+    fn from(flags: u64) -> CompileFlags {
+        // Two bits per type size, starting from bit `shift`.
+        let bits = |shift: u32| match (flags >> shift) & 0b11 {
+            0b00 => Some(16),
+            0b01 => Some(32),
+            0b10 => Some(64),
+            _ => None,
+        };
+        let flag = |bit: u32| flags & (1 << bit) != 0;
+        CompileFlags {
+            uint_bits: bits(0),
+            ulong_bits: bits(2),
+            pointer_bits: bits(4),
+            z_off_bits: bits(6),
+            debug: flag(8),
+            asm: flag(9),
+            winapi: flag(10),
+            build_fixed: flag(12),
+            dynamic_crc_table: flag(13),
+            gz_compress: !flag(16),
+            gzip: !flag(17),
+            pkzip_bug_workaround: flag(20),
+            fastest: flag(21),
+        }
+    }
+}
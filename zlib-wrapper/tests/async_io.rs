@@ -0,0 +1,147 @@
+#![cfg(feature = "async")]
+
+mod common;
+
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use common::{lorem, numbers};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, ReadBuf};
+use zlib_wrapper::*;
+
+// Hands out a byte at a time, and makes every other poll wait.
+struct Trickle<'a> {
+    data: &'a [u8],
+    ready: bool,
+}
+
+impl AsyncRead for Trickle<'_> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        self.ready = !self.ready;
+        if !self.ready {
+            cx.waker().wake_by_ref();
+            return Poll::Pending;
+        }
+        if let Some((&first, rest)) = self.data.split_first() {
+            buf.put_slice(&[first]);
+            self.data = rest;
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+// Takes a few bytes at a time, and makes every other poll wait.
+#[derive(Default)]
+struct Sink {
+    data: Vec<u8>,
+    ready: bool,
+    shut_down: bool,
+}
+
+impl AsyncWrite for Sink {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        self.ready = !self.ready;
+        if !self.ready {
+            cx.waker().wake_by_ref();
+            return Poll::Pending;
+        }
+        let taken = buf.len().min(7);
+        self.data.extend_from_slice(&buf[..taken]);
+        Poll::Ready(Ok(taken))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        self.shut_down = true;
+        Poll::Ready(Ok(()))
+    }
+}
+
+fn runtime() -> tokio::runtime::Runtime {
+    tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap()
+}
+
+fn assert_send<T: Send>() {}
+
+#[test]
+fn adapters_are_send() {
+    assert_send::<AsyncGzDecoder<&[u8]>>();
+    assert_send::<AsyncMultiGzDecoder<&[u8]>>();
+    assert_send::<AsyncGzEncoder<Vec<u8>>>();
+}
+
+#[test]
+fn gz_decoder() {
+    let lorem = lorem();
+    let gzipped = gzip_compress(&lorem, CompressionLevel::Default).unwrap();
+    let trickle = Trickle {
+        data: &gzipped,
+        ready: false,
+    };
+    let mut decoder = AsyncGzDecoder::new(trickle).unwrap();
+    let mut decompressed = Vec::new();
+    runtime()
+        .block_on(decoder.read_to_end(&mut decompressed))
+        .unwrap();
+    assert_eq!(decompressed, lorem.as_bytes());
+    assert!(decoder.header().is_some());
+}
+
+#[test]
+fn gz_decoder_truncated() {
+    // A truncated member is an error, not a short read.
+    let gzipped = gzip_compress(lorem(), CompressionLevel::Default).unwrap();
+    let mut decoder = AsyncGzDecoder::new(&gzipped[..gzipped.len() - 4]).unwrap();
+    let err = runtime()
+        .block_on(decoder.read_to_end(&mut Vec::new()))
+        .unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::UnexpectedEof);
+}
+
+#[test]
+fn multi_gz_decoder() {
+    let big = numbers().repeat(30);
+    let parallel = compress_parallel(big.as_bytes(), CompressionLevel::Default, 4).unwrap();
+    let mut decoder = AsyncMultiGzDecoder::new(&parallel[..]).unwrap();
+    let mut streamed = Vec::new();
+    runtime()
+        .block_on(decoder.read_to_end(&mut streamed))
+        .unwrap();
+    assert_eq!(streamed, big.as_bytes());
+}
+
+#[test]
+fn gz_encoder() {
+    let numbers = numbers();
+    let runtime = runtime();
+    let mut encoder = AsyncGzEncoder::new(Sink::default(), CompressionLevel::Default).unwrap();
+    runtime.block_on(async {
+        encoder.write_all(numbers.as_bytes()).await.unwrap();
+        encoder.flush().await.unwrap();
+    });
+    // Everything written so far decompresses from what reached the sink.
+    let mut inflater = Inflater::new(Format::Gzip).unwrap();
+    let mut inflated = vec![0u8; numbers.len()];
+    let (_, produced, done) = inflater
+        .decompress(&encoder.get_ref().data, &mut inflated)
+        .unwrap();
+    assert_eq!((produced, done), (numbers.len(), false));
+    runtime.block_on(encoder.shutdown()).unwrap();
+    assert!(encoder.get_ref().shut_down);
+    assert!(runtime.block_on(encoder.write_all(b"more")).is_err());
+    let sink = encoder.into_inner();
+    assert_eq!(gzip_decompress(&sink.data).unwrap(), numbers.as_bytes());
+}
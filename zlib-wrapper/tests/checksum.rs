@@ -0,0 +1,63 @@
+mod common;
+
+use std::hash::Hasher;
+
+use common::{HELLO, lorem};
+use zlib_wrapper::*;
+
+#[test]
+fn crc32_check_value() {
+    assert_eq!(crc32(b"123456789"), 0xcbf43926);
+    assert_eq!(crc32(b""), 0);
+    let mut hasher = Crc32::default();
+    hasher.write(b"123456789");
+    assert_eq!(hasher.finish(), 0xcbf43926);
+}
+
+#[test]
+fn crc32_in_pieces() {
+    let lorem = lorem();
+    let mut crc = Crc32::new();
+    for chunk in lorem.as_bytes().chunks(7) {
+        crc.update(chunk);
+    }
+    assert_eq!(crc.finalize(), crc32(lorem.as_bytes()));
+    let (head, tail) = lorem.as_bytes().split_at(1000);
+    assert_eq!(
+        crc32_combine(crc32(head), crc32(tail), tail.len() as u64),
+        crc32(lorem.as_bytes())
+    );
+}
+
+#[test]
+fn adler32_check_value() {
+    assert_eq!(adler32(b"Wikipedia"), 0x11e60398);
+    assert_eq!(adler32(b""), 1);
+    assert_eq!(Adler32::new().finalize(), 1);
+}
+
+#[test]
+fn adler32_in_pieces() {
+    let lorem = lorem();
+    let mut adler = Adler32::new();
+    for chunk in lorem.as_bytes().chunks(7) {
+        adler.update(chunk);
+    }
+    assert_eq!(adler.finalize(), adler32(lorem.as_bytes()));
+    let (head, tail) = lorem.as_bytes().split_at(1000);
+    assert_eq!(
+        adler32_combine(adler32(head), adler32(tail), tail.len() as u64),
+        adler32(lorem.as_bytes())
+    );
+}
+
+#[test]
+fn zlib_trailer_is_the_adler32() {
+    // A zlib stream ends with the big-endian Adler-32 of the uncompressed data.
+    let compressed = zlib_compress(HELLO).unwrap();
+    let trailer = &compressed[compressed.len() - 4..];
+    assert_eq!(
+        u32::from_be_bytes(trailer.try_into().unwrap()),
+        adler32(HELLO)
+    );
+}
@@ -0,0 +1,110 @@
+// Data and helpers the tests share. Not every test file uses all of it.
+#![allow(dead_code)]
+
+use std::ffi::c_void;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicIsize, Ordering};
+
+use zlib_wrapper::{Allocator, Deflater};
+
+pub const HELLO: &[u8] = b"hello, zlib, no exclamation mark";
+
+pub fn lorem() -> String {
+    "Lorem ipsum dolor sit amet, consectetur adipiscing elit. ".repeat(100)
+}
+
+// Exactly two read_gz_file_bytes buffers worth of data.
+pub fn pages() -> Vec<u8> {
+    b"0123456789abcdef".repeat(0x2000 / 16)
+}
+
+// About 110 KB of text that doesn't repeat, so it compresses like real data.
+pub fn numbers() -> String {
+    (0..20000).map(|i| format!("{i} ")).collect()
+}
+
+// Bytes from a xorshift generator don't compress.
+pub fn noise() -> Vec<u8> {
+    let mut state = 0x2545f491u32;
+    (0..256)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state as u8
+        })
+        .collect()
+}
+
+// A gz file checked in next to Cargo.toml: file.txt.gz was made by gzip(1),
+// multibyte.txt.gz holds "-" and 4096 "ж", each straddling gzread chunks.
+pub fn fixture(name: &str) -> String {
+    format!("{}/{}", env!("CARGO_MANIFEST_DIR"), name)
+}
+
+pub fn multibyte() -> String {
+    "-".to_string() + &"ж".repeat(4096) + "\n"
+}
+
+// A path in `dir` as the `&str` the gz functions take.
+pub fn path_in(dir: &tempfile::TempDir, name: &str) -> String {
+    dir.path().join(name).to_str().unwrap().to_string()
+}
+
+pub fn path_buf_in(dir: &tempfile::TempDir, name: &str) -> PathBuf {
+    dir.path().join(name)
+}
+
+// `file.txt.gz` with a flipped bit in its CRC-32 footer, written to `dir`.
+pub fn corrupted_gz(dir: &tempfile::TempDir) -> String {
+    let mut corrupted = std::fs::read(fixture("file.txt.gz")).unwrap();
+    let crc_offset = corrupted.len() - 8;
+    corrupted[crc_offset] ^= 1;
+    let path = path_in(dir, "corrupted.gz");
+    std::fs::write(&path, corrupted).unwrap();
+    path
+}
+
+pub fn exists(path: impl AsRef<Path>) -> bool {
+    path.as_ref().exists()
+}
+
+// A reader handing out at most 7 bytes per read.
+pub struct Trickle<'a>(pub &'a [u8]);
+
+impl Read for Trickle<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let len = buf.len().min(self.0.len()).min(7);
+        buf[..len].copy_from_slice(&self.0[..len]);
+        self.0 = &self.0[len..];
+        Ok(len)
+    }
+}
+
+// Counts what's currently allocated, on top of malloc/free.
+pub struct Counting(pub Arc<AtomicIsize>);
+
+impl Allocator for Counting {
+    fn alloc(&self, size: usize) -> *mut c_void {
+        self.0.fetch_add(1, Ordering::Relaxed);
+        unsafe { libc::malloc(size) }
+    }
+    fn free(&self, ptr: *mut c_void) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+        unsafe { libc::free(ptr) }
+    }
+}
+
+// Compresses all of `input` with one call each, into a big enough buffer.
+pub fn deflate_all(deflater: &mut Deflater, input: &[u8]) -> Vec<u8> {
+    let mut output = vec![0u8; deflater.bound(input.len())];
+    let (consumed, mut produced) = deflater.compress(input, &mut output).unwrap();
+    let (finished, done) = deflater.finish(&mut output[produced..]).unwrap();
+    produced += finished;
+    assert_eq!(consumed, input.len());
+    assert!(done);
+    output.truncate(produced);
+    output
+}
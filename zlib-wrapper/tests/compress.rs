@@ -0,0 +1,164 @@
+mod common;
+
+use std::borrow::Cow;
+
+use common::{HELLO, deflate_all, lorem, noise, pages};
+use zlib_wrapper::*;
+
+#[test]
+fn round_trip() {
+    let compressed = zlib_compress(HELLO).unwrap();
+    assert!(compressed.len() <= compress_bound(HELLO.len()));
+    assert_eq!(zlib_uncompress(&compressed, 100).unwrap(), HELLO);
+    // Strings and vectors go in as they are.
+    assert_eq!(
+        zlib_compress("hello, zlib, no exclamation mark").unwrap(),
+        compressed
+    );
+    let owned = format!("hello, {}, no exclamation mark", "zlib").into_bytes();
+    assert_eq!(zlib_compress(owned).unwrap(), compressed);
+}
+
+#[test]
+fn compress_into_appends() {
+    let mut dest = b"prefix".to_vec();
+    let written = zlib_compress_into(HELLO, &mut dest).unwrap();
+    let compressed = zlib_compress(HELLO).unwrap();
+    assert_eq!(&dest[..6], b"prefix");
+    assert_eq!(&dest[6..], compressed);
+    assert_eq!(written, compressed.len());
+}
+
+#[test]
+fn compress_cow_stores_what_doesnt_shrink() {
+    let noise = noise();
+    assert!(matches!(zlib_compress_cow(&noise), Ok(Cow::Borrowed(stored)) if stored == noise));
+    // A single short message doesn't shrink either, a few of them do.
+    assert!(matches!(zlib_compress_cow(HELLO), Ok(Cow::Borrowed(_))));
+    let hellos = HELLO.repeat(4);
+    let hellos_cow = zlib_compress_cow(&hellos).unwrap();
+    assert!(matches!(&hellos_cow, Cow::Owned(_)));
+    assert_eq!(zlib_uncompress(&hellos_cow, hellos.len()).unwrap(), hellos);
+    assert!(matches!(zlib_compress_cow(b""), Ok(Cow::Borrowed(b""))));
+}
+
+#[test]
+fn empty_input_is_a_complete_stream() {
+    let empty = zlib_compress(b"").unwrap();
+    assert!(!empty.is_empty());
+    assert!(empty.len() <= compress_bound(0));
+    assert_eq!(zlib_uncompress(&empty, 0).unwrap(), b"");
+    assert_eq!(zlib_uncompress_auto(&empty).unwrap(), b"");
+    assert_eq!(zlib_uncompress(b"", 0), Err(ZlibError::DataError));
+}
+
+#[test]
+fn uncompress_errors() {
+    let compressed = zlib_compress(HELLO).unwrap();
+    // The buffer is too small to hold the uncompressed data.
+    assert_eq!(zlib_uncompress(&compressed, 0), Err(ZlibError::BufError));
+    assert_eq!(zlib_uncompress(&compressed, 10), Err(ZlibError::BufError));
+    // zlib hits the end of the input before the end of the stream.
+    let truncated = &compressed[..compressed.len() / 2];
+    assert!(zlib_uncompress(truncated, 100).is_err());
+    // Garbage instead of a zlib header.
+    assert_eq!(
+        zlib_uncompress(b"definitely not zlib", 100),
+        Err(ZlibError::DataError)
+    );
+}
+
+#[test]
+fn uncompress_auto_grows_the_buffer() {
+    // Zeros compress roughly 1000:1, so this takes several retries.
+    let zeros = vec![0u8; 1 << 20];
+    let compressed = zlib_compress(&zeros).unwrap();
+    assert_eq!(zlib_uncompress_auto(&compressed).unwrap(), zeros);
+}
+
+#[test]
+fn compression_levels() {
+    let lorem = lorem();
+    let stored = zlib_compress_level(&lorem, CompressionLevel::None).unwrap();
+    let fast = zlib_compress_level(&lorem, CompressionLevel::Fast).unwrap();
+    let best = zlib_compress_level(&lorem, CompressionLevel::Best).unwrap();
+    assert!(stored.len() > lorem.len());
+    assert!(best.len() <= fast.len() && fast.len() < stored.len());
+    assert_eq!(
+        zlib_uncompress(&best, lorem.len()).unwrap(),
+        lorem.as_bytes()
+    );
+    assert_eq!(
+        zlib_compress_level(&lorem, CompressionLevel::Default).unwrap(),
+        zlib_compress(&lorem).unwrap()
+    );
+    assert_eq!(
+        zlib_compress_level(&lorem, CompressionLevel::Level(9)),
+        Ok(best)
+    );
+    assert_eq!(
+        zlib_compress_level(&lorem, CompressionLevel::Level(10)),
+        Err(ZlibError::StreamError)
+    );
+}
+
+#[test]
+fn uncompress_limited() {
+    // 16 MB of zeros compress to about 16 KB.
+    let bomb = zlib_compress(vec![0u8; 16 << 20]).unwrap();
+    assert!(bomb.len() < 20 << 10);
+    assert_eq!(
+        zlib_uncompress_limited(&bomb, Some(1 << 20)),
+        Err(ZlibError::LimitExceeded)
+    );
+    assert_eq!(
+        zlib_uncompress_limited(&bomb, Some(16 << 20))
+            .unwrap()
+            .len(),
+        16 << 20
+    );
+    let compressed = zlib_compress(HELLO).unwrap();
+    assert_eq!(
+        zlib_uncompress_limited(&compressed, Some(HELLO.len())).unwrap(),
+        HELLO
+    );
+}
+
+#[test]
+fn decompress_auto_detects_the_format() {
+    let lorem = lorem();
+    let gzipped = gzip_compress(&lorem, CompressionLevel::Default).unwrap();
+    let zlibbed = zlib_compress(&lorem).unwrap();
+    let mut deflater = Deflater::new(CompressionLevel::Default, Format::Raw).unwrap();
+    let raw = deflate_all(&mut deflater, lorem.as_bytes());
+    for compressed in [&gzipped, &zlibbed, &raw] {
+        assert_eq!(decompress_auto(compressed).unwrap(), lorem.as_bytes());
+    }
+    // Raw deflate that happens to start like a zlib header: a stored block
+    // with the padding bits clear is 0x08, and a length of 29 (0x1d) makes
+    // 0x08 0x1d a multiple of 31. The zlib attempt fails and raw is tried next.
+    let pages = pages();
+    let mut stored = vec![0x08, 0x1d, 0x00, 0xe2, 0xff];
+    stored.extend_from_slice(&pages[..29]);
+    stored.extend_from_slice(&[0x01, 0x00, 0x00, 0xff, 0xff]);
+    assert!(u16::from_be_bytes([stored[0], stored[1]]).is_multiple_of(31));
+    assert_eq!(decompress_auto(&stored).unwrap(), pages[..29]);
+    assert_eq!(
+        decompress_auto(b"not compressed"),
+        Err(ZlibError::DataError)
+    );
+    assert_eq!(decompress_auto(b""), Err(ZlibError::DataError));
+}
+
+#[test]
+fn store() {
+    // Already compressed data doesn't shrink, storing it is only the framing.
+    let compressed = zlib_compress(common::numbers()).unwrap();
+    let stored = zlib_store(&compressed).unwrap();
+    assert!(stored.len() <= compress_bound(compressed.len()));
+    assert_eq!(
+        zlib_uncompress(&stored, compressed.len()).unwrap(),
+        compressed
+    );
+    assert_eq!(zlib_uncompress(&zlib_store(b"").unwrap(), 1).unwrap(), b"");
+}
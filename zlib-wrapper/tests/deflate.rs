@@ -0,0 +1,419 @@
+mod common;
+
+use std::io::{Read, Write};
+use std::ptr;
+use std::sync::atomic::{AtomicIsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use common::{Counting, HELLO, Trickle, deflate_all, lorem, numbers, pages};
+use zlib_wrapper::*;
+
+#[test]
+fn small_pieces_through_a_tiny_buffer() {
+    let lorem = lorem();
+    let mut deflater = Deflater::new(CompressionLevel::Default, Format::Zlib).unwrap();
+    let mut output = [0u8; 16];
+    let mut deflated = Vec::new();
+    for mut input in lorem.as_bytes().chunks(100) {
+        while !input.is_empty() {
+            let (consumed, produced) = deflater.compress(input, &mut output).unwrap();
+            deflated.extend_from_slice(&output[..produced]);
+            input = &input[consumed..];
+        }
+    }
+    loop {
+        let (produced, done) = deflater.finish(&mut output).unwrap();
+        deflated.extend_from_slice(&output[..produced]);
+        if done {
+            break;
+        }
+    }
+    // zlib-ng's output depends on how the input was split.
+    #[cfg(not(feature = "zlib-ng"))]
+    assert_eq!(deflated, zlib_compress(&lorem).unwrap());
+    assert_eq!(zlib_uncompress_auto(&deflated).unwrap(), lorem.as_bytes());
+}
+
+#[test]
+fn one_call_each_and_reset() {
+    let lorem = lorem();
+    let mut deflater = Deflater::new(CompressionLevel::Default, Format::Zlib).unwrap();
+    assert_eq!(
+        deflate_all(&mut deflater, lorem.as_bytes()),
+        zlib_compress(&lorem).unwrap()
+    );
+    // The same stream can compress another message after a reset.
+    deflater.reset().unwrap();
+    assert_eq!(
+        deflate_all(&mut deflater, HELLO),
+        zlib_compress(HELLO).unwrap()
+    );
+}
+
+#[test]
+fn raw_is_zlib_without_the_framing() {
+    let lorem = lorem();
+    let zlibbed = zlib_compress(&lorem).unwrap();
+    let mut deflater = Deflater::new(CompressionLevel::Default, Format::Raw).unwrap();
+    let raw = deflate_all(&mut deflater, lorem.as_bytes());
+    assert_eq!(raw, zlibbed[2..zlibbed.len() - 4]);
+    assert!(Deflater::new(CompressionLevel::Default, Format::GzipOrZlib).is_err());
+}
+
+#[test]
+fn dictionary() {
+    // A dictionary with the common words makes a short message compress better.
+    let dict = b"consectetur adipiscing dolor ipsum lorem amet elit sit";
+    let message = b"lorem ipsum dolor sit amet, consectetur adipiscing elit";
+    let mut deflater = Deflater::new(CompressionLevel::Best, Format::Zlib).unwrap();
+    deflater.set_dictionary(dict).unwrap();
+    let with_dict = deflate_all(&mut deflater, message);
+    let without_dict = zlib_compress_level(message, CompressionLevel::Best).unwrap();
+    assert!(with_dict.len() < without_dict.len());
+    // Only before the first byte goes in.
+    let mut deflater = Deflater::new(CompressionLevel::Best, Format::Zlib).unwrap();
+    deflater.compress(message, &mut [0u8; 16]).unwrap();
+    assert_eq!(deflater.set_dictionary(dict), Err(ZlibError::StreamError));
+}
+
+#[test]
+fn options() {
+    let lorem = lorem();
+    // Little memory and run-length matching only, still decompresses the same.
+    let options = DeflateOptions {
+        mem_level: 1,
+        strategy: Strategy::Rle,
+    };
+    let mut deflater =
+        Deflater::with_options(CompressionLevel::Best, Format::Zlib, options).unwrap();
+    let deflated = deflate_all(&mut deflater, lorem.as_bytes());
+    assert_eq!(zlib_uncompress_auto(&deflated).unwrap(), lorem.as_bytes());
+
+    let mut encoder =
+        ZlibEncoder::with_options(Vec::new(), CompressionLevel::Best, options).unwrap();
+    encoder.write_all(lorem.as_bytes()).unwrap();
+    assert_eq!(
+        zlib_uncompress_auto(&encoder.finish().unwrap()).unwrap(),
+        lorem.as_bytes()
+    );
+
+    for mem_level in [0, 10] {
+        let options = DeflateOptions {
+            mem_level,
+            ..DeflateOptions::default()
+        };
+        assert_eq!(
+            Deflater::with_options(CompressionLevel::Default, Format::Zlib, options).err(),
+            Some(ZlibError::StreamError)
+        );
+    }
+}
+
+#[test]
+fn set_params_halfway() {
+    // Stop compressing halfway through, e.g. for a section of random data.
+    let lorem = lorem();
+    let mut deflater = Deflater::new(CompressionLevel::Best, Format::Raw).unwrap();
+    let mut deflated = vec![0u8; deflater.bound(lorem.len() * 2)];
+    let (_, mut produced) = deflater.compress(lorem.as_bytes(), &mut deflated).unwrap();
+    assert_eq!(
+        deflater.set_params(CompressionLevel::None, Strategy::HuffmanOnly, &mut []),
+        Err(ZlibError::BufError)
+    );
+    produced += deflater
+        .set_params(
+            CompressionLevel::None,
+            Strategy::HuffmanOnly,
+            &mut deflated[produced..],
+        )
+        .unwrap();
+    let (_, stored) = deflater
+        .compress(lorem.as_bytes(), &mut deflated[produced..])
+        .unwrap();
+    produced += stored;
+    let (finished, done) = deflater.finish(&mut deflated[produced..]).unwrap();
+    assert!(done);
+    let mut inflater = Inflater::new(Format::Raw).unwrap();
+    let mut inflated = vec![0u8; lorem.len() * 2];
+    let (_, inflated_len, done) = inflater
+        .decompress(&deflated[..produced + finished], &mut inflated)
+        .unwrap();
+    assert!(done);
+    assert_eq!(&inflated[..inflated_len], lorem.repeat(2).as_bytes());
+}
+
+#[test]
+fn on_progress() {
+    let pages = pages();
+    let progress = Arc::new(Mutex::new(Vec::new()));
+    let mut encoder = GzEncoder::new(Vec::new(), CompressionLevel::Fast).unwrap();
+    let report = progress.clone();
+    encoder.set_on_progress(Some(Box::new(move |consumed, produced| {
+        report.lock().unwrap().push((consumed, produced));
+    })));
+    for page in pages.chunks(0x1000) {
+        encoder.write_all(page).unwrap();
+    }
+    let gzipped = encoder.finish().unwrap();
+    let progress = progress.lock().unwrap();
+    // Both totals only grow, and end with everything in and out.
+    assert!(
+        progress
+            .windows(2)
+            .all(|w| w[0].0 <= w[1].0 && w[0].1 <= w[1].1)
+    );
+    assert_eq!(
+        progress.last(),
+        Some(&(pages.len() as u64, gzipped.len() as u64))
+    );
+}
+
+#[test]
+fn allocator() {
+    let lorem = lorem();
+    let live = Arc::new(AtomicIsize::new(0));
+    let mut deflater = Deflater::with_allocator(
+        CompressionLevel::Best,
+        Format::Zlib,
+        DeflateOptions::default(),
+        Box::new(Counting(live.clone())),
+    )
+    .unwrap();
+    let deflated = deflate_all(&mut deflater, lorem.as_bytes());
+    assert!(live.load(Ordering::Relaxed) > 0);
+    drop(deflater);
+    assert_eq!(live.load(Ordering::Relaxed), 0);
+
+    let mut inflater =
+        Inflater::with_allocator(Format::Zlib, Box::new(Counting(live.clone()))).unwrap();
+    let mut inflated = vec![0u8; lorem.len()];
+    let (_, produced, done) = inflater.decompress(&deflated, &mut inflated).unwrap();
+    assert_eq!((produced, done), (lorem.len(), true));
+    assert!(live.load(Ordering::Relaxed) > 0);
+    drop(inflater);
+    assert_eq!(live.load(Ordering::Relaxed), 0);
+}
+
+#[test]
+fn exhausted_allocator() {
+    // An allocator out of memory makes the stream fail to start.
+    struct Exhausted;
+    impl Allocator for Exhausted {
+        fn alloc(&self, _: usize) -> *mut std::ffi::c_void {
+            ptr::null_mut()
+        }
+        fn free(&self, _: *mut std::ffi::c_void) {}
+    }
+    assert_eq!(
+        Inflater::with_allocator(Format::Zlib, Box::new(Exhausted)).err(),
+        Some(ZlibError::MemError)
+    );
+}
+
+#[test]
+fn pending() {
+    let lorem = lorem();
+    let mut deflater = Deflater::new(CompressionLevel::Best, Format::Raw).unwrap();
+    assert_eq!(deflater.pending(), Ok((0, 0)));
+    let mut compressed = vec![0u8; lorem.len()];
+    deflater
+        .compress(lorem.as_bytes(), &mut compressed)
+        .unwrap();
+    // Finishing into a tiny buffer leaves the rest of the stream pending.
+    let mut flushed = [0u8; 4];
+    let (produced, done) = deflater.finish(&mut flushed).unwrap();
+    assert_eq!((produced, done), (flushed.len(), false));
+    let (pending, bits) = deflater.pending().unwrap();
+    assert!(pending > 0 && (0..8).contains(&bits));
+}
+
+#[test]
+fn prime() {
+    // Start the raw stream with an empty block of fixed codes: BFINAL 0,
+    // BTYPE 01 and the 7-bit end of block code.
+    let lorem = lorem();
+    let mut deflater = Deflater::new(CompressionLevel::Best, Format::Raw).unwrap();
+    deflater.prime(10, 0b010).unwrap();
+    let mut deflated = vec![0u8; deflater.bound(lorem.len()) + 2];
+    let (_, produced) = deflater.compress(lorem.as_bytes(), &mut deflated).unwrap();
+    let (finished, done) = deflater.finish(&mut deflated[produced..]).unwrap();
+    assert!(done);
+    let mut inflater = Inflater::new(Format::Raw).unwrap();
+    let mut inflated = vec![0u8; lorem.len()];
+    let (_, produced, done) = inflater
+        .decompress(&deflated[..produced + finished], &mut inflated)
+        .unwrap();
+    assert_eq!((produced, done), (lorem.len(), true));
+    assert_eq!(inflated, lorem.as_bytes());
+    assert_eq!(deflater.prime(17, 0), Err(ZlibError::StreamError));
+}
+
+#[test]
+fn tune() {
+    // Level 9's parameters but with a much shorter search, still valid output.
+    let pages = pages();
+    let mut deflater = Deflater::new(CompressionLevel::Best, Format::Zlib).unwrap();
+    deflater.tune(32, 258, 258, 16).unwrap();
+    let deflated = deflate_all(&mut deflater, &pages);
+    assert_eq!(zlib_uncompress(&deflated, pages.len()).unwrap(), pages);
+}
+
+#[test]
+fn try_clone_branches() {
+    // Compress the shared prefix once, then finish two different endings.
+    let lorem = lorem();
+    let header = GzipHeaderBuilder::new().name("branch").build();
+    let mut prefix = Deflater::new(CompressionLevel::Best, Format::Gzip).unwrap();
+    prefix.set_header(&header).unwrap();
+    let mut branch_output = vec![0u8; prefix.bound(lorem.len() + 16)];
+    let (_, prefix_len) = prefix
+        .compress(lorem.as_bytes(), &mut branch_output)
+        .unwrap();
+    let branch_output = &branch_output[..prefix_len];
+    let finish_branch = |mut deflater: Deflater, ending: &[u8]| {
+        let mut output = vec![0u8; deflater.bound(lorem.len() + ending.len())];
+        let (_, produced) = deflater.compress(ending, &mut output).unwrap();
+        let (finished, done) = deflater.finish(&mut output[produced..]).unwrap();
+        assert!(done);
+        [branch_output, &output[..produced + finished]].concat()
+    };
+    let ending_a = finish_branch(prefix.try_clone().unwrap(), b" - the end");
+    // The original goes first, the clone has its own copy of the header.
+    let clone = prefix.try_clone().unwrap();
+    drop(prefix);
+    let ending_b = finish_branch(clone, b" - another end");
+    let (decoded, decoded_header) = gzip_decompress_with_header(&ending_a).unwrap();
+    assert_eq!(decoded, [lorem.as_bytes(), b" - the end"].concat());
+    assert_eq!(decoded_header, header);
+    let (decoded, decoded_header) = gzip_decompress_with_header(&ending_b).unwrap();
+    assert_eq!(decoded, [lorem.as_bytes(), b" - another end"].concat());
+    assert_eq!(decoded_header, header);
+}
+
+#[test]
+fn try_clone_allocator() {
+    let live = Arc::new(AtomicIsize::new(0));
+    let deflater = Deflater::with_allocator(
+        CompressionLevel::Default,
+        Format::Zlib,
+        DeflateOptions::default(),
+        Box::new(Counting(live.clone())),
+    )
+    .unwrap();
+    let clone = deflater.try_clone().unwrap();
+    drop(deflater);
+    assert!(live.load(Ordering::Relaxed) > 0);
+    drop(clone);
+    assert_eq!(live.load(Ordering::Relaxed), 0);
+}
+
+#[test]
+fn streams_move_between_threads() {
+    // A stream moves to a worker thread and back, allocator and all.
+    let lorem = lorem();
+    let live = Arc::new(AtomicIsize::new(0));
+    let deflater = Deflater::with_allocator(
+        CompressionLevel::Best,
+        Format::Zlib,
+        DeflateOptions::default(),
+        Box::new(Counting(live.clone())),
+    )
+    .unwrap();
+    let input = lorem.clone();
+    let worker = std::thread::spawn(move || {
+        let mut deflater = deflater;
+        let deflated = deflate_all(&mut deflater, input.as_bytes());
+        (deflater, deflated)
+    });
+    let (deflater, deflated) = worker.join().unwrap();
+    assert_eq!(zlib_uncompress_auto(&deflated).unwrap(), lorem.as_bytes());
+    drop(deflater);
+    assert_eq!(live.load(Ordering::Relaxed), 0);
+
+    let mut inflater = Inflater::new(Format::Zlib).unwrap();
+    let len = lorem.len();
+    let inflated = std::thread::spawn(move || {
+        let mut inflated = vec![0u8; len];
+        let (_, produced, done) = inflater.decompress(&deflated, &mut inflated).unwrap();
+        assert_eq!((produced, done), (len, true));
+        inflated
+    })
+    .join()
+    .unwrap();
+    assert_eq!(inflated, lorem.as_bytes());
+}
+
+#[test]
+fn checksum() {
+    let lorem = lorem();
+    let mut deflater = Deflater::new(CompressionLevel::Default, Format::Gzip).unwrap();
+    let (first_half, second_half) = lorem.as_bytes().split_at(lorem.len() / 2);
+    let mut output = vec![0u8; compress_bound(lorem.len()) + 18];
+    let (_, produced) = deflater.compress(first_half, &mut output).unwrap();
+    // zlib-ng folds the CRC-32 of gzip streams as it goes and only has the
+    // value once the stream is finished.
+    #[cfg(not(feature = "zlib-ng"))]
+    assert_eq!(deflater.checksum(), crc32(first_half));
+    let (_, more) = deflater
+        .compress(second_half, &mut output[produced..])
+        .unwrap();
+    let (rest, done) = deflater.finish(&mut output[produced + more..]).unwrap();
+    assert!(done);
+    output.truncate(produced + more + rest);
+    assert_eq!(
+        crc32_combine(
+            crc32(first_half),
+            crc32(second_half),
+            second_half.len() as u64
+        ),
+        deflater.checksum()
+    );
+    let mut inflated = vec![0u8; lorem.len()];
+    let mut inflater = Inflater::new(Format::GzipOrZlib).unwrap();
+    let (_, produced, done) = inflater.decompress(&output, &mut inflated).unwrap();
+    assert_eq!((produced, done), (lorem.len(), true));
+    assert_eq!(inflater.checksum(), crc32(lorem.as_bytes()));
+
+    let zlib = zlib_compress(&lorem).unwrap();
+    inflater.reset().unwrap();
+    inflater.decompress(&zlib, &mut inflated).unwrap();
+    assert_eq!(inflater.checksum(), adler32(lorem.as_bytes()));
+    let mut deflater = Deflater::new(CompressionLevel::Default, Format::Zlib).unwrap();
+    deflater.compress(lorem.as_bytes(), &mut output).unwrap();
+    assert_eq!(deflater.checksum(), adler32(lorem.as_bytes()));
+}
+
+#[test]
+fn compress_parallel_members() {
+    let big = numbers().repeat(30);
+    let parallel = compress_parallel(big.as_bytes(), CompressionLevel::Default, 4).unwrap();
+    // One gzip member per block, they decompress as one.
+    assert_eq!(gzip_decompress(&parallel).unwrap(), big.as_bytes());
+    assert_eq!(gzip_verify(&parallel).unwrap(), crc32(big.as_bytes()));
+    // Streaming, it takes a decoder that carries on past the first member.
+    let mut streamed = Vec::new();
+    MultiGzDecoder::new(Trickle(&parallel))
+        .unwrap()
+        .read_to_end(&mut streamed)
+        .unwrap();
+    assert_eq!(streamed, big.as_bytes());
+    // A single member decoder stops at the end of the first block.
+    let mut first_block = Vec::new();
+    GzDecoder::new(&parallel[..])
+        .unwrap()
+        .read_to_end(&mut first_block)
+        .unwrap();
+    assert_eq!(first_block, &big.as_bytes()[..0x100000]);
+}
+
+#[test]
+fn compress_parallel_edges() {
+    assert_eq!(
+        compress_parallel(b"", CompressionLevel::Default, 4).unwrap(),
+        gzip_compress(b"", CompressionLevel::Default).unwrap()
+    );
+    assert_eq!(
+        compress_parallel(b"", CompressionLevel::Default, 0),
+        Err(ZlibError::StreamError)
+    );
+}
@@ -0,0 +1,381 @@
+mod common;
+
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom, Write};
+#[cfg(unix)]
+use std::os::unix::io::IntoRawFd;
+
+use common::{corrupted_gz, fixture, lorem, multibyte, numbers, pages, path_in};
+use zlib_wrapper::*;
+
+#[test]
+fn read_gz_file_contents() {
+    let path = fixture("file.txt.gz");
+    let contents = read_gz_file(&path).unwrap();
+    assert!(!contents.is_empty());
+    assert_eq!(read_gz_file_bytes(&path).unwrap(), contents.as_bytes());
+    // After the 1-byte "-", every 2-byte "ж" in this one starts at an odd offset,
+    // so the one starting at 4095 straddles the first 4096-byte gzread chunk.
+    assert_eq!(
+        read_gz_file(&fixture("multibyte.txt.gz")).unwrap(),
+        multibyte()
+    );
+    assert!(matches!(
+        read_gz_file(&fixture("no-such-file.txt.gz")),
+        Err(ZlibError::Errno(_))
+    ));
+}
+
+#[test]
+fn read_gz_file_buffers() {
+    let dir = tempfile::tempdir().unwrap();
+    let pages = pages();
+    let pages_gz = path_in(&dir, "pages.gz");
+    let mut writer = GzWriter::create(&pages_gz).unwrap();
+    writer.write_all(&pages).unwrap();
+    drop(writer);
+    assert_eq!(read_gz_file_bytes(&pages_gz).unwrap(), pages);
+    // Binary contents aren't a string.
+    let noise_gz = path_in(&dir, "noise.gz");
+    let noise = gzip_compress(common::noise(), CompressionLevel::Default).unwrap();
+    std::fs::write(&noise_gz, noise).unwrap();
+    assert_eq!(read_gz_file(&noise_gz), Err(ZlibError::DataError));
+
+    let big = numbers().repeat(30);
+    let big_gz = path_in(&dir, "big.gz");
+    std::fs::write(
+        &big_gz,
+        gzip_compress(&big, CompressionLevel::Default).unwrap(),
+    )
+    .unwrap();
+    for buf_size in [1, 0x1000, 0x40000] {
+        assert_eq!(read_gz_file_with_capacity(&big_gz, buf_size).unwrap(), big);
+    }
+    assert_eq!(
+        read_gz_file_with_capacity(&big_gz, 0),
+        Err(ZlibError::StreamError)
+    );
+}
+
+#[test]
+fn reader_lines() {
+    let path = fixture("file.txt.gz");
+    let lines: Vec<String> = BufReader::new(GzReader::open(&path).unwrap())
+        .lines()
+        .map(Result::unwrap)
+        .collect();
+    assert_eq!(
+        lines.concat(),
+        read_gz_file(&path).unwrap().replace('\n', "")
+    );
+}
+
+#[test]
+fn reader_seek() {
+    let multibyte = multibyte();
+    let mut contents = String::new();
+    let mut reader = GzReader::open(&fixture("multibyte.txt.gz")).unwrap();
+    reader.set_buffer_size(0x40000).unwrap();
+    reader.read_to_string(&mut contents).unwrap();
+    assert_eq!(reader.set_buffer_size(0x40000), Err(ZlibError::StreamError));
+    assert_eq!(contents, multibyte);
+
+    // "ж" is 2 bytes long, skip the "-" and the first 4000 of them.
+    assert_eq!(reader.tell().unwrap(), contents.len() as u64);
+    assert_eq!(reader.seek(SeekFrom::Start(8001)).unwrap(), 8001);
+    assert_eq!(reader.tell().unwrap(), 8001);
+    contents.clear();
+    reader.read_to_string(&mut contents).unwrap();
+    assert_eq!(contents, "ж".repeat(96) + "\n");
+    assert!(reader.eof());
+    assert!(reader.seek(SeekFrom::End(0)).is_err());
+
+    reader.rewind().unwrap();
+    assert_eq!(reader.tell().unwrap(), 0);
+    contents.clear();
+    reader.read_to_string(&mut contents).unwrap();
+    assert_eq!(contents, multibyte);
+}
+
+#[cfg(unix)]
+#[test]
+fn reader_from_raw_fd() {
+    let fd = std::fs::File::open(fixture("multibyte.txt.gz"))
+        .unwrap()
+        .into_raw_fd();
+    let mut reader = GzReader::from_raw_fd(fd, GzMode::Read).unwrap();
+    let mut contents = String::new();
+    reader.read_to_string(&mut contents).unwrap();
+    assert_eq!(contents, multibyte());
+}
+
+#[test]
+fn reader_read_returns_everything() {
+    let dir = tempfile::tempdir().unwrap();
+    let pages = pages();
+    let pages_gz = path_in(&dir, "pages.gz");
+    std::fs::write(
+        &pages_gz,
+        gzip_compress(&pages, CompressionLevel::Default).unwrap(),
+    )
+    .unwrap();
+    let mut reader = GzReader::open(&pages_gz).unwrap();
+    let mut read = vec![0u8; pages.len() + 1];
+    assert_eq!(reader.read(&mut read).unwrap(), pages.len());
+    assert_eq!(&read[..pages.len()], pages);
+}
+
+#[test]
+fn lines() {
+    // The only line in this one is longer than the gzgets buffer.
+    let mut lines = gz_lines(&fixture("multibyte.txt.gz")).unwrap();
+    assert_eq!(lines.next(), Some(Ok("-".to_string() + &"ж".repeat(4096))));
+    assert_eq!(lines.next(), None);
+    // A NUL doesn't end the line, only a newline does.
+    let dir = tempfile::tempdir().unwrap();
+    let nul_gz = path_in(&dir, "nul.txt.gz");
+    std::fs::write(
+        &nul_gz,
+        gzip_compress(b"a\0b\nc\n", CompressionLevel::Default).unwrap(),
+    )
+    .unwrap();
+    let lines: Result<Vec<String>, ZlibError> = gz_lines(&nul_gz).unwrap().collect();
+    assert_eq!(lines.unwrap(), ["a\0b", "c"]);
+}
+
+#[test]
+fn writer_and_append() {
+    let dir = tempfile::tempdir().unwrap();
+    let hello_gz = path_in(&dir, "hello.txt.gz");
+    let mut writer = GzWriter::create(&hello_gz).unwrap();
+    writer.set_buffer_size(0x100).unwrap();
+    writer.write_all(b"hello, gzip\n").unwrap();
+    assert_eq!(writer.tell().unwrap(), 12);
+    writer.flush().unwrap();
+    writer
+        .write_fmt_gz(format_args!("no {} either", "exclamation mark"))
+        .unwrap();
+    writeln!(writer).unwrap();
+    drop(writer);
+    assert_eq!(
+        read_gz_file(&hello_gz).unwrap(),
+        "hello, gzip\nno exclamation mark either\n"
+    );
+
+    let mut writer = GzWriter::open(&hello_gz, GzMode::Append(CompressionLevel::Best)).unwrap();
+    writer
+        .set_params(CompressionLevel::None, Strategy::Default)
+        .unwrap();
+    writer.write_all(b"appended").unwrap();
+    writer.flush_mode(FlushMode::FullFlush).unwrap();
+    assert_eq!(writer.put_str("\n"), Ok(1));
+    assert_eq!(writer.put_str("nul\0"), Err(ZlibError::StreamError));
+    drop(writer);
+    // Now the file is two gzip members, gzread reads through both.
+    assert_eq!(
+        read_gz_file(&hello_gz).unwrap(),
+        "hello, gzip\nno exclamation mark either\nappended\n"
+    );
+    let lines: Result<Vec<String>, ZlibError> = gz_lines(&hello_gz).unwrap().collect();
+    assert_eq!(
+        lines.unwrap(),
+        ["hello, gzip", "no exclamation mark either", "appended"]
+    );
+
+    assert!(GzWriter::open(&hello_gz, GzMode::Read).is_err());
+    assert_eq!(
+        GzWriter::open(&hello_gz, GzMode::Write(CompressionLevel::Level(10))).err(),
+        Some(ZlibError::StreamError)
+    );
+}
+
+#[test]
+fn gzip_compress_reads_back() {
+    let dir = tempfile::tempdir().unwrap();
+    let lorem = lorem();
+    let lorem_gz = path_in(&dir, "lorem.txt.gz");
+    std::fs::write(
+        &lorem_gz,
+        gzip_compress(&lorem, CompressionLevel::Best).unwrap(),
+    )
+    .unwrap();
+    assert_eq!(read_gz_file(&lorem_gz).unwrap(), lorem);
+}
+
+#[test]
+fn corrupted() {
+    let dir = tempfile::tempdir().unwrap();
+    let corrupted_gz = corrupted_gz(&dir);
+    assert_eq!(read_gz_file_bytes(&corrupted_gz), Err(ZlibError::DataError));
+    assert!(gz_lines(&corrupted_gz).unwrap().any(|line| line.is_err()));
+    let mut reader = GzReader::open(&corrupted_gz).unwrap();
+    assert_eq!(reader.last_error(), None);
+    assert!(reader.read_to_end(&mut Vec::new()).is_err());
+    // Z_DATA_ERROR
+    assert_eq!(reader.last_error().unwrap().0, -3);
+    reader.clear_error();
+    assert_eq!(reader.last_error(), None);
+}
+
+#[test]
+fn truncated() {
+    // One cut off halfway, gzread ends early but leaves an error behind.
+    let dir = tempfile::tempdir().unwrap();
+    let counted: String = (0..20000).map(|i| format!("{}\n", i)).collect();
+    let full = gzip_compress(&counted, CompressionLevel::Default).unwrap();
+    let truncated_gz = path_in(&dir, "truncated.gz");
+    std::fs::write(&truncated_gz, &full[..full.len() / 2]).unwrap();
+    assert_eq!(read_gz_file_bytes(&truncated_gz), Err(ZlibError::DataError));
+    assert!(gz_lines(&truncated_gz).unwrap().any(|line| line.is_err()));
+    let mut reader = GzReader::open(&truncated_gz).unwrap();
+    let mut partial = Vec::new();
+    let err = reader.read_to_end(&mut partial).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::UnexpectedEof);
+    assert!(!partial.is_empty() && counted.as_bytes().starts_with(&partial));
+    // Z_BUF_ERROR, "unexpected end of file"
+    assert_eq!(reader.last_error().unwrap().0, -5);
+}
+
+#[test]
+fn writer_close() {
+    let dir = tempfile::tempdir().unwrap();
+    let pages = pages();
+    let pages_gz = path_in(&dir, "pages.gz");
+    let mut writer = GzWriter::create(&pages_gz).unwrap();
+    writer.write_all(&pages).unwrap();
+    assert_eq!(writer.close(), Ok(()));
+    assert_eq!(read_gz_file_bytes(&pages_gz).unwrap(), pages);
+}
+
+// Writes to /dev/full fail with ENOSPC, gzwrite only buffers, the error
+// turns up when the data is flushed on close.
+#[cfg(target_os = "linux")]
+#[test]
+fn writer_close_reports_the_flush() {
+    let mut writer = GzWriter::create("/dev/full").unwrap();
+    writer.write_all(b"no room").unwrap();
+    assert_eq!(writer.close(), Err(ZlibError::Errno(libc::ENOSPC)));
+}
+
+// Every `write` reports what gzwrite took, even the one that failed to
+// write out the buffered data, the one after fails with the OS error.
+#[cfg(target_os = "linux")]
+#[test]
+fn writer_write_reports_what_it_took() {
+    let pages = pages();
+    let mut writer = GzWriter::open("/dev/full", GzMode::Write(CompressionLevel::None)).unwrap();
+    writer.set_buffer_size(0x1000).unwrap();
+    let mut written = 0;
+    let err = loop {
+        match writer.write(&pages) {
+            Ok(n) => {
+                assert!(n > 0 && n <= pages.len());
+                written += n;
+            }
+            Err(err) => break err,
+        }
+    };
+    assert_eq!(err.raw_os_error(), Some(libc::ENOSPC));
+    assert_eq!(writer.tell().unwrap(), written as u64);
+    // The error sticks.
+    assert!(writer.write(b"more").is_err());
+}
+
+#[test]
+fn bytes() {
+    let dir = tempfile::tempdir().unwrap();
+    let bytes_gz = path_in(&dir, "bytes.gz");
+    let mut writer = GzWriter::create(&bytes_gz).unwrap();
+    for b in 0..=255 {
+        writer.write_byte(b).unwrap();
+    }
+    writer.close().unwrap();
+    let mut reader = GzReader::open(&bytes_gz).unwrap();
+    // 0xff comes back as itself, not as the -1 that marks the end.
+    let read: Vec<u8> = std::iter::from_fn(|| reader.read_byte()).collect();
+    assert_eq!(read, (0..=255).collect::<Vec<u8>>());
+    assert_eq!(reader.read_byte(), None);
+    assert_eq!(reader.last_error(), None);
+
+    let mut reader = GzReader::open(&bytes_gz).unwrap();
+    let peeked = reader.read_byte().unwrap();
+    reader.unread_byte(peeked).unwrap();
+    assert_eq!(reader.read_byte(), Some(0));
+    assert_eq!(reader.read_byte(), Some(1));
+    // Any byte goes back, and `read` sees it too.
+    reader.unread_byte(b'x').unwrap();
+    let mut next = [0u8; 3];
+    reader.read_exact(&mut next).unwrap();
+    assert_eq!(next, [b'x', 2, 3]);
+    // Until zlib runs out of room for them.
+    let pushed = (0..).find(|_| reader.unread_byte(b'y').is_err()).unwrap();
+    assert!(pushed >= 1);
+    // Z_DATA_ERROR
+    assert_eq!(reader.last_error().unwrap().0, -3);
+}
+
+#[test]
+fn items() {
+    // Fixed-size records, stored little-endian to read back the same anywhere.
+    let dir = tempfile::tempdir().unwrap();
+    let records: Vec<[u32; 3]> = (0..1000u32)
+        .map(|i| [i, i * i, u32::MAX - i].map(u32::to_le))
+        .collect();
+    let records_gz = path_in(&dir, "records.gz");
+    let mut writer = GzWriter::create(&records_gz).unwrap();
+    assert_eq!(writer.write_items(&records).unwrap(), records.len());
+    // A stray byte at the end, half a record.
+    writer.write_items(&[0xabu8]).unwrap();
+    writer.close().unwrap();
+    let mut reader = GzReader::open(&records_gz).unwrap();
+    let mut read = vec![[0u32; 3]; 600];
+    assert_eq!(reader.read_items(&mut read).unwrap(), 600);
+    assert_eq!(read, records[..600]);
+    assert_eq!(reader.read_items(&mut read).unwrap(), 400);
+    assert_eq!(read[..400], records[600..]);
+    assert!(reader.eof());
+    assert_eq!(reader.read_items(&mut read).unwrap(), 0);
+    assert_eq!(u32::from_le(records[999][2]), u32::MAX - 999);
+}
+
+#[test]
+fn compressed_offset() {
+    let dir = tempfile::tempdir().unwrap();
+    let big = numbers().repeat(30);
+    let offset_gz = path_in(&dir, "offset.gz");
+    let mut writer = GzWriter::create(&offset_gz).unwrap();
+    assert_eq!(writer.compressed_offset().unwrap(), 0);
+    writer.write_all(big.as_bytes()).unwrap();
+    writer.flush().unwrap();
+    // Uncompressed bytes in, compressed bytes out.
+    let flushed = writer.compressed_offset().unwrap();
+    assert_eq!(writer.tell().unwrap(), big.len() as u64);
+    assert!(flushed > 0 && flushed < big.len() as u64);
+    writer.close().unwrap();
+    let file_len = std::fs::metadata(&offset_gz).unwrap().len();
+    assert!(flushed < file_len);
+
+    let mut reader = GzReader::open(&offset_gz).unwrap();
+    assert_eq!(reader.compressed_offset().unwrap(), 0);
+    let mut half = vec![0u8; big.len() / 2];
+    reader.read_exact(&mut half).unwrap();
+    let midway = reader.compressed_offset().unwrap();
+    assert_eq!(reader.tell().unwrap(), half.len() as u64);
+    assert!(midway > 0 && midway < file_len);
+    reader.read_to_end(&mut Vec::new()).unwrap();
+    assert_eq!(reader.compressed_offset().unwrap(), file_len);
+}
+
+#[test]
+fn is_direct() {
+    // A plain text file reads fine, as is.
+    let dir = tempfile::tempdir().unwrap();
+    let lorem = lorem();
+    let plain = path_in(&dir, "plain.txt");
+    std::fs::write(&plain, &lorem).unwrap();
+    let mut reader = GzReader::open(&plain).unwrap();
+    assert!(reader.is_direct());
+    let mut contents = String::new();
+    reader.read_to_string(&mut contents).unwrap();
+    assert_eq!(contents, lorem);
+    assert!(!GzReader::open(&fixture("file.txt.gz")).unwrap().is_direct());
+}
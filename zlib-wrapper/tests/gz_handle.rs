@@ -0,0 +1,32 @@
+// On its own, the descriptor count would see the other tests' files come and go.
+#![cfg(target_os = "linux")]
+
+mod common;
+
+use std::io::Read;
+
+use common::corrupted_gz;
+use zlib_wrapper::*;
+
+// Every open gz file holds a descriptor, a leaked handle shows up as one more.
+#[test]
+fn errors_close_the_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let corrupted_gz = corrupted_gz(&dir);
+    let open_fds = || std::fs::read_dir("/proc/self/fd").unwrap().count();
+    let before = open_fds();
+    assert_eq!(read_gz_file_bytes(&corrupted_gz), Err(ZlibError::DataError));
+    let mut reader = GzReader::open(&corrupted_gz).unwrap();
+    assert!(reader.read_to_end(&mut Vec::new()).is_err());
+    drop(reader);
+    assert!(gz_lines(&corrupted_gz).unwrap().any(|line| line.is_err()));
+    // Quiet the default hook, the panic is expected.
+    std::panic::set_hook(Box::new(|_| {}));
+    let panicked = std::panic::catch_unwind(|| {
+        let _reader = GzReader::open(&corrupted_gz).unwrap();
+        panic!("midway");
+    });
+    let _ = std::panic::take_hook();
+    assert!(panicked.is_err());
+    assert_eq!(open_fds(), before);
+}
@@ -0,0 +1,310 @@
+mod common;
+
+use common::{HELLO, deflate_all, fixture, lorem, numbers, pages};
+use zlib_wrapper::*;
+
+#[test]
+fn small_pieces_through_a_tiny_buffer() {
+    let lorem = lorem();
+    let deflated = zlib_compress(&lorem).unwrap();
+    let mut inflater = Inflater::new(Format::Zlib).unwrap();
+    let mut output = [0u8; 16];
+    let mut inflated = Vec::new();
+    let mut input = &deflated[..];
+    loop {
+        let (consumed, produced, done) = inflater.decompress(input, &mut output).unwrap();
+        inflated.extend_from_slice(&output[..produced]);
+        input = &input[consumed..];
+        if done {
+            break;
+        }
+    }
+    assert_eq!(inflated, lorem.as_bytes());
+}
+
+#[test]
+fn reset() {
+    // Reuse the same stream for the next message, which is raw deflate.
+    let lorem = lorem();
+    let mut deflater = Deflater::new(CompressionLevel::Default, Format::Raw).unwrap();
+    let raw = deflate_all(&mut deflater, lorem.as_bytes());
+    let mut inflater = Inflater::new(Format::Zlib).unwrap();
+    let mut inflated = vec![0u8; lorem.len()];
+    let zlibbed = zlib_compress(&lorem).unwrap();
+    let (_, _, done) = inflater.decompress(&zlibbed, &mut inflated).unwrap();
+    assert!(done);
+    inflater.reset_format(Format::Raw).unwrap();
+    let (consumed, produced, done) = inflater.decompress(&raw, &mut inflated).unwrap();
+    assert_eq!((consumed, produced, done), (raw.len(), lorem.len(), true));
+    assert_eq!(inflated, lorem.as_bytes());
+    assert_eq!(inflater.total_in(), raw.len() as u64);
+
+    inflater.reset().unwrap();
+    let (_, produced, done) = inflater.decompress(&raw, &mut inflated).unwrap();
+    assert_eq!((produced, done), (lorem.len(), true));
+}
+
+#[test]
+fn not_zlib() {
+    let mut inflater = Inflater::new(Format::Zlib).unwrap();
+    assert_eq!(
+        inflater.decompress(b"definitely not zlib", &mut [0u8; 16]),
+        Err(ZlibError::DataError)
+    );
+}
+
+#[test]
+fn dictionary() {
+    let dict = b"consectetur adipiscing dolor ipsum lorem amet elit sit";
+    let message = b"lorem ipsum dolor sit amet, consectetur adipiscing elit";
+    let mut deflater = Deflater::new(CompressionLevel::Best, Format::Zlib).unwrap();
+    deflater.set_dictionary(dict).unwrap();
+    let with_dict = deflate_all(&mut deflater, message);
+    // Without the dictionary zlib can't go past the header.
+    let mut inflater = Inflater::new(Format::Zlib).unwrap();
+    let mut inflated = vec![0u8; message.len()];
+    assert_eq!(
+        inflater.decompress(&with_dict, &mut inflated),
+        Err(ZlibError::NeedDict)
+    );
+    assert_eq!(inflater.set_dictionary(b"wrong"), Err(ZlibError::DataError));
+    inflater.set_dictionary(dict).unwrap();
+    let rest = &with_dict[inflater.total_in() as usize..];
+    let (_, produced, done) = inflater.decompress(rest, &mut inflated).unwrap();
+    assert!(done);
+    assert_eq!(&inflated[..produced], message);
+    assert_eq!(inflater.total_out(), message.len() as u64);
+}
+
+#[test]
+fn gzip_members() {
+    // Two gzip members back to back decompress to both contents joined.
+    let lorem = lorem();
+    let mut members = gzip_compress(HELLO, CompressionLevel::Default).unwrap();
+    members.extend(gzip_compress(&lorem, CompressionLevel::Best).unwrap());
+    let joined = gzip_decompress(&members).unwrap();
+    assert_eq!(joined, [HELLO, lorem.as_bytes()].concat());
+    assert_eq!(
+        gzip_decompress(&members[..members.len() - 1]),
+        Err(ZlibError::DataError)
+    );
+    // zlib itself writes a bare header.
+    let (_, header) = gzip_decompress_with_header(&members).unwrap();
+    assert_eq!((header.name, header.mtime), (None, 0));
+}
+
+#[test]
+fn gzip_header_from_gzip() {
+    // file.txt.gz was made by gzip(1), which keeps the original name and mtime.
+    let path = fixture("file.txt.gz");
+    let (contents, header) = gzip_decompress_with_header(&std::fs::read(&path).unwrap()).unwrap();
+    assert_eq!(contents, read_gz_file_bytes(&path).unwrap());
+    assert_eq!(header.name.as_deref(), Some("file.txt"));
+    assert_eq!(
+        (header.comment, header.mtime, header.os),
+        (None, 1748793800, 3)
+    );
+    let mut inflater = Inflater::new(Format::Zlib).unwrap();
+    assert_eq!(inflater.track_header(), Err(ZlibError::StreamError));
+}
+
+#[test]
+fn gzip_header_round_trip() {
+    let lorem = lorem();
+    let header = GzipHeaderBuilder::new()
+        .name("lorem.txt")
+        .comment("from the tests")
+        .mtime(1700000000)
+        .build();
+    let named = gzip_compress_with_header(&lorem, CompressionLevel::Best, &header).unwrap();
+    assert_eq!(
+        gzip_decompress_with_header(&named).unwrap(),
+        (lorem.as_bytes().to_vec(), header)
+    );
+    // Reproducible output is the same whenever it's made.
+    let reproducible = |mtime| {
+        let header = GzipHeaderBuilder::new()
+            .name("lorem.txt")
+            .mtime(mtime)
+            .reproducible(true)
+            .build();
+        gzip_compress_with_header(&lorem, CompressionLevel::Best, &header).unwrap()
+    };
+    assert_eq!(reproducible(1), reproducible(2));
+    let (_, header) = gzip_decompress_with_header(&reproducible(1)).unwrap();
+    assert_eq!(header.mtime, 0);
+}
+
+#[test]
+fn gzip_header_errors() {
+    let mut deflater = Deflater::new(CompressionLevel::Default, Format::Zlib).unwrap();
+    assert_eq!(
+        deflater.set_header(&GzipHeader::default()),
+        Err(ZlibError::StreamError)
+    );
+    let mut deflater = Deflater::new(CompressionLevel::Default, Format::Gzip).unwrap();
+    let header = GzipHeaderBuilder::new().name("nul\0").build();
+    assert_eq!(deflater.set_header(&header), Err(ZlibError::StreamError));
+}
+
+#[test]
+fn try_clone() {
+    // Decompress up to a snapshot, then carry on from the original and a copy.
+    let pages = pages();
+    let header = GzipHeaderBuilder::new().name("branch").build();
+    let gzipped = gzip_compress_with_header(&pages, CompressionLevel::Best, &header).unwrap();
+    let (first, rest) = gzipped.split_at(gzipped.len() / 2);
+    let mut inflater = Inflater::new(Format::Gzip).unwrap();
+    inflater.track_header().unwrap();
+    let mut inflated = vec![0u8; pages.len()];
+    let (consumed, snapshot_at, _) = inflater.decompress(first, &mut inflated).unwrap();
+    assert_eq!(consumed, first.len());
+    let carry_on = |mut inflater: Inflater| {
+        let mut rest_inflated = inflated[..snapshot_at].to_vec();
+        rest_inflated.resize(pages.len(), 0);
+        let (_, produced, done) = inflater
+            .decompress(rest, &mut rest_inflated[snapshot_at..])
+            .unwrap();
+        assert_eq!((snapshot_at + produced, done), (pages.len(), true));
+        assert_eq!(inflater.header(), Some(header.clone()));
+        assert_eq!(inflater.total_out(), pages.len() as u64);
+        rest_inflated
+    };
+    let clone = inflater.try_clone().unwrap();
+    assert_eq!(carry_on(clone), pages);
+    assert_eq!(carry_on(inflater), pages);
+}
+
+#[test]
+fn max_ratio() {
+    // The bomb trips the ratio long before the output would reach its size.
+    let bomb = zlib_compress(vec![0u8; 16 << 20]).unwrap();
+    let mut inflater = Inflater::new(Format::Zlib).unwrap();
+    inflater.set_max_ratio(Some(100));
+    let mut inflated = vec![0u8; 0x8000];
+    let mut input = &bomb[..];
+    let err = loop {
+        match inflater.decompress(input, &mut inflated) {
+            Ok((consumed, _, _)) => input = &input[consumed..],
+            Err(err) => break err,
+        }
+    };
+    assert_eq!(err, ZlibError::LimitExceeded);
+    assert!(inflater.total_out() < 1 << 20);
+}
+
+#[test]
+fn gzip_footer() {
+    // inflate checks the CRC-32 and ISIZE footer itself, a flipped bit anywhere
+    // fails instead of returning the damaged data.
+    let pages = pages();
+    let member = gzip_compress(&pages, CompressionLevel::Best).unwrap();
+    assert_eq!(gzip_decompress(&member).unwrap(), pages);
+    let crc_at = member.len() - 8;
+    let isize_at = member.len() - 4;
+    for at in [crc_at, isize_at, member.len() / 2] {
+        let mut damaged = member.clone();
+        damaged[at] ^= 0x10;
+        assert_eq!(gzip_decompress(&damaged), Err(ZlibError::DataError));
+    }
+    // ISIZE is the length modulo 2^32, little-endian.
+    let isize_bytes: [u8; 4] = member[isize_at..].try_into().unwrap();
+    assert_eq!(u32::from_le_bytes(isize_bytes), pages.len() as u32);
+    let crc_bytes: [u8; 4] = member[crc_at..isize_at].try_into().unwrap();
+    assert_eq!(u32::from_le_bytes(crc_bytes), crc32(&pages));
+}
+
+#[test]
+fn gzip_verify_members() {
+    let pages = pages();
+    let member = gzip_compress(&pages, CompressionLevel::Best).unwrap();
+    assert_eq!(gzip_verify(&member), Ok(crc32(&pages)));
+    let mut two = member.clone();
+    two.extend_from_slice(&gzip_compress(b"tail", CompressionLevel::Fast).unwrap());
+    let content = [&pages[..], b"tail"].concat();
+    assert_eq!(gzip_verify(&two), Ok(crc32(&content)));
+    let mut damaged = member.clone();
+    damaged[member.len() - 8] ^= 0x10;
+    assert_eq!(gzip_verify(&damaged), Err(ZlibError::DataError));
+    assert_eq!(
+        gzip_verify(&member[..member.len() - 1]),
+        Err(ZlibError::DataError)
+    );
+}
+
+#[test]
+fn gzip_or_zlib() {
+    // zlib tells gzip and zlib apart by the header itself, one configuration
+    // takes both, here even through the same stream after a reset.
+    let lorem = lorem();
+    let gzipped = gzip_compress(&lorem, CompressionLevel::Default).unwrap();
+    let zlibbed = zlib_compress(&lorem).unwrap();
+    let mut inflater = Inflater::new(Format::GzipOrZlib).unwrap();
+    let mut inflated = vec![0u8; lorem.len() + 1];
+    for compressed in [&gzipped, &zlibbed] {
+        inflater.reset().unwrap();
+        let (consumed, produced, done) = inflater.decompress(compressed, &mut inflated).unwrap();
+        assert_eq!((consumed, done), (compressed.len(), true));
+        assert_eq!(&inflated[..produced], lorem.as_bytes());
+    }
+    // Raw deflate has no header to detect.
+    let mut deflater = Deflater::new(CompressionLevel::Default, Format::Raw).unwrap();
+    let raw = deflate_all(&mut deflater, lorem.as_bytes());
+    inflater.reset().unwrap();
+    assert_eq!(
+        inflater.decompress(&raw, &mut inflated),
+        Err(ZlibError::DataError)
+    );
+}
+
+#[test]
+fn set_validate() {
+    // A zlib stream with a broken Adler-32 in its trailer.
+    let lorem = lorem();
+    let mut corrupted = zlib_compress(&lorem).unwrap();
+    *corrupted.last_mut().unwrap() ^= 1;
+    let mut inflated = vec![0u8; lorem.len()];
+    let mut inflater = Inflater::new(Format::Zlib).unwrap();
+    assert_eq!(
+        inflater.decompress(&corrupted, &mut inflated),
+        Err(ZlibError::DataError)
+    );
+    // Without validation the data comes out, the trailer is skipped unchecked.
+    inflater.set_validate(false).unwrap();
+    inflater.reset_format(Format::Zlib).unwrap();
+    let (_, produced, done) = inflater.decompress(&corrupted, &mut inflated).unwrap();
+    assert_eq!((produced, done), (lorem.len(), true));
+    assert_eq!(inflated, lorem.as_bytes());
+    inflater.set_validate(true).unwrap();
+    inflater.reset().unwrap();
+    assert_eq!(
+        inflater.decompress(&corrupted, &mut inflated),
+        Err(ZlibError::DataError)
+    );
+}
+
+#[test]
+fn get_dictionary() {
+    let numbers = numbers();
+    let mut inflater = Inflater::new(Format::Raw).unwrap();
+    assert_eq!(inflater.get_dictionary().unwrap(), b"");
+    let mut deflater = Deflater::new(CompressionLevel::Default, Format::Raw).unwrap();
+    let first = deflate_all(&mut deflater, numbers.as_bytes());
+    let mut inflated = vec![0u8; numbers.len()];
+    let (_, produced, done) = inflater.decompress(&first, &mut inflated).unwrap();
+    assert_eq!((produced, done), (numbers.len(), true));
+    let window = inflater.get_dictionary().unwrap();
+    assert_eq!(window, &numbers.as_bytes()[numbers.len() - 0x8000..]);
+    // The next piece refers back into the window, a fresh inflater primed
+    // with it picks up from there.
+    let tail = &numbers.as_bytes()[numbers.len() - 0x1000..];
+    let mut deflater = Deflater::new(CompressionLevel::Default, Format::Raw).unwrap();
+    deflater.set_dictionary(&window).unwrap();
+    let second = deflate_all(&mut deflater, tail);
+    assert!(second.len() < 0x100);
+    let mut inflater = Inflater::new(Format::Raw).unwrap();
+    inflater.set_dictionary(&window).unwrap();
+    let (_, produced, done) = inflater.decompress(&second, &mut inflated).unwrap();
+    assert_eq!((produced, done), (0x1000, true));
+    assert_eq!(&inflated[..0x1000], tail);
+}
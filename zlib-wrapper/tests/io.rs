@@ -0,0 +1,330 @@
+mod common;
+
+use std::io::{BufReader, Read, Write};
+use std::path::Path;
+
+use common::{HELLO, Trickle, corrupted_gz, fixture, lorem, noise, numbers, pages, path_buf_in};
+use zlib_wrapper::*;
+
+fn assert_send<T: Send>() {}
+
+// The adapters are `Send` when what they wrap is.
+#[test]
+fn adapters_are_send() {
+    assert_send::<Deflater>();
+    assert_send::<Inflater>();
+    assert_send::<GzEncoder<Vec<u8>>>();
+    assert_send::<ZlibEncoder<Vec<u8>>>();
+    assert_send::<GzDecoder<&[u8]>>();
+    assert_send::<MultiGzDecoder<&[u8]>>();
+    assert_send::<ZlibDecoder<&[u8]>>();
+}
+
+#[test]
+fn gz_encoder() {
+    let lorem = lorem();
+    let mut encoder = GzEncoder::new(Vec::new(), CompressionLevel::Best).unwrap();
+    for word in lorem.split_inclusive(' ') {
+        encoder.write_all(word.as_bytes()).unwrap();
+    }
+    // After a flush, what reached the inner writer already decompresses to all of it.
+    encoder.flush().unwrap();
+    let mut inflater = Inflater::new(Format::Gzip).unwrap();
+    let mut inflated = vec![0u8; lorem.len()];
+    let (_, produced, done) = inflater
+        .decompress(encoder.get_ref(), &mut inflated)
+        .unwrap();
+    assert_eq!((produced, done), (lorem.len(), false));
+    let gzipped = encoder.finish().unwrap();
+    assert_eq!(gzip_decompress(&gzipped).unwrap(), lorem.as_bytes());
+}
+
+#[test]
+fn gz_encoder_with_header_into_a_file() {
+    // Larger than the encoder's buffer, and through a file.
+    let dir = tempfile::tempdir().unwrap();
+    let pages = pages();
+    let encoded_gz = path_buf_in(&dir, "encoded.gz");
+    let file = std::fs::File::create(&encoded_gz).unwrap();
+    let header = GzipHeaderBuilder::new().name("pages").build();
+    let mut encoder = GzEncoder::with_header(file, CompressionLevel::None, &header).unwrap();
+    encoder.write_all(&pages).unwrap();
+    encoder.finish().unwrap();
+    let encoded = std::fs::read(&encoded_gz).unwrap();
+    assert_eq!(
+        gzip_decompress_with_header(&encoded).unwrap(),
+        (pages, header)
+    );
+}
+
+#[test]
+fn gz_encoder_finishes_on_drop() {
+    let mut gzipped = Vec::new();
+    let mut encoder = GzEncoder::new(&mut gzipped, CompressionLevel::Default).unwrap();
+    encoder.write_all(HELLO).unwrap();
+    drop(encoder);
+    assert_eq!(gzip_decompress(&gzipped).unwrap(), HELLO);
+}
+
+#[test]
+fn gz_encoder_moves_between_threads() {
+    let encoder = GzEncoder::new(Vec::new(), CompressionLevel::Fast).unwrap();
+    let gzipped = std::thread::spawn(move || encoder.finish().unwrap())
+        .join()
+        .unwrap();
+    assert_eq!(gzip_decompress(&gzipped).unwrap(), b"");
+}
+
+#[test]
+fn gz_decoder() {
+    let gzipped = gzip_compress(HELLO, CompressionLevel::Default).unwrap();
+    let mut decoded = Vec::new();
+    GzDecoder::new(Trickle(&gzipped))
+        .unwrap()
+        .read_to_end(&mut decoded)
+        .unwrap();
+    assert_eq!(decoded, HELLO);
+
+    let path = fixture("file.txt.gz");
+    let file = std::fs::File::open(&path).unwrap();
+    let mut decoded = String::new();
+    GzDecoder::new(BufReader::new(file))
+        .unwrap()
+        .read_to_string(&mut decoded)
+        .unwrap();
+    assert_eq!(decoded, read_gz_file(&path).unwrap());
+}
+
+#[test]
+fn gz_decoder_stops_after_the_member() {
+    // Whatever follows the member is left alone.
+    let pages = pages();
+    let header = GzipHeaderBuilder::new().name("pages").build();
+    let mut trailing = gzip_compress_with_header(&pages, CompressionLevel::None, &header).unwrap();
+    trailing.extend_from_slice(b"garbage after the member");
+    let mut decoder = GzDecoder::new(&trailing[..]).unwrap();
+    let mut decoded = Vec::new();
+    decoder.read_to_end(&mut decoded).unwrap();
+    assert_eq!(decoded, pages);
+    assert_eq!(decoder.header().unwrap().name.as_deref(), Some("pages"));
+    assert_eq!(decoder.read(&mut [0u8; 16]).unwrap(), 0);
+}
+
+#[test]
+fn gz_decoder_errors() {
+    let encoded = gzip_compress(pages(), CompressionLevel::None).unwrap();
+    let truncated = &encoded[..encoded.len() - 4];
+    let err = GzDecoder::new(truncated)
+        .unwrap()
+        .read_to_end(&mut Vec::new())
+        .unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::UnexpectedEof);
+    assert!(
+        GzDecoder::new(&b"not gzip at all"[..])
+            .unwrap()
+            .read_to_end(&mut Vec::new())
+            .is_err()
+    );
+    // A flipped bit anywhere in the member fails the read.
+    let member = gzip_compress(pages(), CompressionLevel::Best).unwrap();
+    for at in [member.len() - 8, member.len() - 4, member.len() / 2] {
+        let mut damaged = member.clone();
+        damaged[at] ^= 0x10;
+        let err = GzDecoder::new(&damaged[..])
+            .unwrap()
+            .read_to_end(&mut Vec::new())
+            .unwrap_err();
+        assert_eq!(ZlibError::from(err), ZlibError::DataError);
+    }
+}
+
+#[test]
+fn zlib_encoder_and_decoder() {
+    let lorem = lorem();
+    let mut encoder = ZlibEncoder::new(Vec::new(), CompressionLevel::Best).unwrap();
+    encoder.write_all(lorem.as_bytes()).unwrap();
+    let zlibbed = encoder.finish().unwrap();
+    // The same stream `zlib_compress` makes in one go.
+    assert_eq!(
+        zlibbed,
+        zlib_compress_level(&lorem, CompressionLevel::Best).unwrap()
+    );
+    let mut decoded = String::new();
+    ZlibDecoder::new(Trickle(&zlibbed))
+        .unwrap()
+        .read_to_string(&mut decoded)
+        .unwrap();
+    assert_eq!(decoded, lorem);
+    let gzipped = gzip_compress(&lorem, CompressionLevel::Best).unwrap();
+    assert!(
+        ZlibDecoder::new(&gzipped[..])
+            .unwrap()
+            .read_to_end(&mut Vec::new())
+            .is_err()
+    );
+}
+
+#[test]
+fn decoder_limits() {
+    let bomb = zlib_compress(vec![0u8; 16 << 20]).unwrap();
+    let mut decoder = ZlibDecoder::new(&bomb[..]).unwrap();
+    decoder.set_max_output(Some(1 << 20));
+    let err = decoder.read_to_end(&mut Vec::new()).unwrap_err();
+    assert_eq!(ZlibError::from(err), ZlibError::LimitExceeded);
+    let mut decoder = ZlibDecoder::new(&bomb[..]).unwrap();
+    decoder.set_max_output(Some(16 << 20));
+    assert_eq!(decoder.read_to_end(&mut Vec::new()).unwrap(), 16 << 20);
+    let mut decoder = ZlibDecoder::new(&bomb[..]).unwrap();
+    decoder.set_max_ratio(None);
+    assert_eq!(decoder.read_to_end(&mut Vec::new()).unwrap(), 16 << 20);
+
+    // Real data is nowhere near the default.
+    let pages = pages();
+    let encoded = gzip_compress(&pages, CompressionLevel::None).unwrap();
+    let mut decoder = GzDecoder::new(&encoded[..]).unwrap();
+    decoder.set_max_ratio(Some(DEFAULT_MAX_RATIO));
+    let mut decoded = Vec::new();
+    decoder.read_to_end(&mut decoded).unwrap();
+    assert_eq!(decoded, pages);
+}
+
+#[test]
+fn multi_gz_decoder() {
+    let big = numbers().repeat(30);
+    let parallel = compress_parallel(big.as_bytes(), CompressionLevel::Default, 4).unwrap();
+    let mut streamed = Vec::new();
+    MultiGzDecoder::new(Trickle(&parallel))
+        .unwrap()
+        .read_to_end(&mut streamed)
+        .unwrap();
+    assert_eq!(streamed, big.as_bytes());
+    // The limit counts every member, not each one on its own.
+    let mut decoder = MultiGzDecoder::new(&parallel[..]).unwrap();
+    decoder.set_max_output(Some(0x180000));
+    let err = decoder.read_to_end(&mut Vec::new()).unwrap_err();
+    assert_eq!(ZlibError::from(err), ZlibError::LimitExceeded);
+    // Anything but another member after one is an error.
+    let mut trailing = parallel.clone();
+    trailing.extend_from_slice(b"garbage");
+    assert!(
+        MultiGzDecoder::new(&trailing[..])
+            .unwrap()
+            .read_to_end(&mut Vec::new())
+            .is_err()
+    );
+}
+
+#[test]
+fn compress_file_keeps_name_and_mtime() {
+    let dir = tempfile::tempdir().unwrap();
+    let pages = pages();
+    let pages_txt = path_buf_in(&dir, "pages.txt");
+    std::fs::write(&pages_txt, &pages).unwrap();
+    let pages_txt_gz = path_buf_in(&dir, "pages.txt.gz");
+    compress_file(&pages_txt, &pages_txt_gz, CompressionLevel::Best).unwrap();
+    assert_eq!(
+        read_gz_file_bytes(pages_txt_gz.to_str().unwrap()).unwrap(),
+        pages
+    );
+    let (_, header) = gzip_decompress_with_header(&std::fs::read(&pages_txt_gz).unwrap()).unwrap();
+    assert_eq!(header.name.as_deref(), Some("pages.txt"));
+    assert!(header.mtime > 0);
+    assert_eq!(
+        compress_file(
+            &path_buf_in(&dir, "missing.txt"),
+            &pages_txt_gz,
+            CompressionLevel::Best
+        ),
+        Err(ZlibError::Errno(libc::ENOENT))
+    );
+}
+
+#[test]
+fn decompress_file_copies() {
+    let dir = tempfile::tempdir().unwrap();
+    let pages = pages();
+    let pages_gz = path_buf_in(&dir, "pages.gz");
+    std::fs::write(
+        &pages_gz,
+        gzip_compress(&pages, CompressionLevel::Default).unwrap(),
+    )
+    .unwrap();
+    let copy = path_buf_in(&dir, "copy.txt");
+    let written = decompress_file(&pages_gz, &copy).unwrap();
+    assert_eq!(written, pages.len() as u64);
+    assert_eq!(std::fs::read(&copy).unwrap(), pages);
+    // Binary contents, which read_gz_file would reject.
+    let noise = noise();
+    let noise_gz = path_buf_in(&dir, "noise.gz");
+    std::fs::write(
+        &noise_gz,
+        gzip_compress(&noise, CompressionLevel::Default).unwrap(),
+    )
+    .unwrap();
+    decompress_file(&noise_gz, &copy).unwrap();
+    assert_eq!(std::fs::read(&copy).unwrap(), noise);
+}
+
+#[test]
+fn decompress_file_errors() {
+    let dir = tempfile::tempdir().unwrap();
+    let copy = path_buf_in(&dir, "copy.txt");
+    let corrupted_gz = corrupted_gz(&dir);
+    assert_eq!(
+        decompress_file(Path::new(&corrupted_gz), &copy),
+        Err(ZlibError::DataError)
+    );
+    let full = gzip_compress(numbers(), CompressionLevel::Default).unwrap();
+    let truncated_gz = path_buf_in(&dir, "truncated.gz");
+    std::fs::write(&truncated_gz, &full[..full.len() / 2]).unwrap();
+    assert_eq!(
+        decompress_file(&truncated_gz, &copy),
+        Err(ZlibError::DataError)
+    );
+}
+
+#[test]
+fn streams() {
+    let pages = pages();
+    let mut gzipped = Vec::new();
+    let read = compress_stream(Trickle(&pages), &mut gzipped, CompressionLevel::Best).unwrap();
+    assert_eq!(read, pages.len() as u64);
+    let mut decompressed = Vec::new();
+    let written = decompress_stream(&gzipped[..], &mut decompressed).unwrap();
+    assert_eq!((written, decompressed), (pages.len() as u64, pages));
+    assert_eq!(
+        decompress_stream(&gzipped[..gzipped.len() - 1], std::io::sink()),
+        Err(ZlibError::DataError)
+    );
+    // Every member, the way gzip -d reads them.
+    let big = numbers().repeat(30);
+    let parallel = compress_parallel(big.as_bytes(), CompressionLevel::Default, 4).unwrap();
+    let mut streamed = Vec::new();
+    decompress_stream(&parallel[..], &mut streamed).unwrap();
+    assert_eq!(streamed, big.as_bytes());
+}
+
+#[cfg(feature = "mmap")]
+#[test]
+fn compress_file_mmap_matches_compress_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let pages_txt = path_buf_in(&dir, "pages.txt");
+    std::fs::write(&pages_txt, pages()).unwrap();
+    let mapped_gz = path_buf_in(&dir, "mapped.txt.gz");
+    compress_file_mmap(&pages_txt, &mapped_gz, CompressionLevel::Default).unwrap();
+    // The same header and data as `compress_file` writes.
+    let pages_txt_gz = path_buf_in(&dir, "pages.txt.gz");
+    compress_file(&pages_txt, &pages_txt_gz, CompressionLevel::Default).unwrap();
+    assert_eq!(
+        std::fs::read(&mapped_gz).unwrap(),
+        std::fs::read(&pages_txt_gz).unwrap()
+    );
+    // Nothing to map in an empty file.
+    let empty = path_buf_in(&dir, "empty.txt");
+    std::fs::write(&empty, b"").unwrap();
+    compress_file_mmap(&empty, &mapped_gz, CompressionLevel::Default).unwrap();
+    assert_eq!(
+        read_gz_file_bytes(mapped_gz.to_str().unwrap()).unwrap(),
+        b""
+    );
+}
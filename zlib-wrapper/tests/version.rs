@@ -0,0 +1,27 @@
+use std::ffi::{c_long, c_uint, c_ulong, c_void};
+
+use zlib_wrapper::*;
+
+#[test]
+fn version_is_compatible() {
+    check_zlib_version().unwrap();
+    assert!(!zlib_version().is_empty());
+}
+
+#[test]
+fn compile_flags_match_the_declarations() {
+    let flags = CompileFlags::from(compile_flags());
+    // The sizes the FFI declarations assume.
+    let bits = |size: usize| Some(size as u32 * 8);
+    assert_eq!(flags.uint_bits, bits(size_of::<c_uint>()));
+    assert_eq!(flags.ulong_bits, bits(size_of::<c_ulong>()));
+    assert_eq!(flags.pointer_bits, bits(size_of::<*mut c_void>()));
+    assert_eq!(flags.z_off_bits, bits(size_of::<c_long>()));
+    assert!(flags.gzip && flags.gz_compress);
+}
+
+#[test]
+fn compile_flags_decode() {
+    assert_eq!(CompileFlags::from(0b11 | 1 << 17).uint_bits, None);
+    assert!(!CompileFlags::from(1 << 17).gzip);
+}